@@ -12,6 +12,32 @@ namespace NAPS2Helper.Features
 {
     public static class ScanningHelper
     {
+        private static readonly System.Text.RegularExpressions.Regex UuidPattern = new(
+            "[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}",
+            System.Text.RegularExpressions.RegexOptions.IgnoreCase);
+        
+        /// <summary>
+        /// A driver-independent key for the physical scanner: its network host,
+        /// or a UUID in its ID, or null if neither is known
+        /// </summary>
+        private static string? DeviceKey(ScanDevice device)
+        {
+            // eSCL reports a connection URI; SANE's escl/airscan IDs embed the URL
+            foreach (var candidate in new[] { device.ConnectionUri, device.ID })
+            {
+                var start = candidate?.IndexOf("://", StringComparison.Ordinal) ?? -1;
+                if (start < 0) continue;
+                var schemeStart = candidate!.LastIndexOfAny(new[] { ':', ' ' }, Math.Max(start - 1, 0)) + 1;
+                if (Uri.TryCreate(candidate.Substring(schemeStart), UriKind.Absolute, out var uri) && uri.Host.Length > 0)
+                {
+                    return "host:" + uri.Host.ToLowerInvariant();
+                }
+            }
+            
+            var uuid = UuidPattern.Match(device.ID);
+            return uuid.Success ? "uuid:" + uuid.Value.ToLowerInvariant() : null;
+        }
+        
         public static async Task<string> ListDevices(string driverName)
         {
             using var scanningContext = new ScanningContext(new MacImageContext());
@@ -33,7 +59,8 @@ namespace NAPS2Helper.Features
                 {
                     Id = device.ID,
                     Name = device.Name,
-                    Driver = device.Driver.ToString()
+                    Driver = device.Driver.ToString(),
+                    Key = DeviceKey(device)
                 });
             }
             
@@ -43,7 +70,7 @@ namespace NAPS2Helper.Features
             });
         }
         
-        public static async Task<string> ScanToImages(string deviceId, string driverName, int dpi, string paperSource)
+        public static async Task<string> ScanToImages(string deviceId, string driverName, int dpi, string paperSource, string? sessionDir = null)
         {
             using var scanningContext = new ScanningContext(new MacImageContext());
             var controller = new ScanController(scanningContext);
@@ -78,9 +105,8 @@ namespace NAPS2Helper.Features
             
             options.Device = device;
             
-            // Create a temp directory for images
-            var tempDir = Path.GetTempPath();
-            var sessionDir = Path.Combine(tempDir, $"naps2_scan_{Guid.NewGuid()}");
+            // Create a temp directory for images, unless the caller chose one
+            sessionDir ??= Path.Combine(Path.GetTempPath(), $"naps2_scan_{Guid.NewGuid()}");
             Directory.CreateDirectory(sessionDir);
             
             // Scan and save images
@@ -105,6 +131,52 @@ namespace NAPS2Helper.Features
             });
         }
 
+        public static async Task<string> GetSources(string deviceId, string driverName)
+        {
+            using var scanningContext = new ScanningContext(new MacImageContext());
+            var controller = new ScanController(scanningContext);
+            
+            Driver driver = Driver.Default;
+            if (!string.IsNullOrEmpty(driverName) && Enum.TryParse<Driver>(driverName, true, out var parsedDriver))
+            {
+                driver = parsedDriver;
+            }
+            
+            var devices = await controller.GetDeviceList(new ScanOptions { Driver = driver });
+            var device = devices.Find(d => d.ID == deviceId);
+            
+            if (device == null)
+            {
+                throw new Exception($"Device with ID {deviceId} not found");
+            }
+            
+            // An empty list tells the caller the driver didn't report its sources
+            var sources = new List<string>();
+            try
+            {
+                var caps = await controller.GetCaps(device);
+                var paperSources = caps.PaperSourceCaps;
+                if (paperSources != null)
+                {
+                    if (paperSources.SupportsFlatbed) sources.Add("Flatbed");
+                    if (paperSources.SupportsFeeder) sources.Add("Feeder");
+                    if (paperSources.SupportsDuplex) sources.Add("Duplex");
+                }
+            }
+            catch (Exception)
+            {
+                sources.Clear();
+            }
+            
+            return JsonSerializer.Serialize(new
+            {
+                Sources = sources
+            }, new JsonSerializerOptions
+            {
+                WriteIndented = true
+            });
+        }
+
         public static string SaveAsJpeg(List<string> imagePaths, string outputDir)
         {
             try
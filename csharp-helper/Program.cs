@@ -72,7 +72,7 @@ namespace NAPS2Helper
         {
             if (args.Length == 0)
             {
-                Console.WriteLine("Missing scan subcommand. Available: list-devices, to-images");
+                Console.WriteLine("Missing scan subcommand. Available: list-devices, to-images, sources");
                 return 1;
             }
             
@@ -98,11 +98,24 @@ namespace NAPS2Helper
                     string driver = args.Length > 2 ? args[2] : string.Empty;
                     int dpi = args.Length > 3 && int.TryParse(args[3], out int parsedDpi) ? parsedDpi : 300;
                     string paperSource = args.Length > 4 ? args[4] : string.Empty;
+                    int tempDirIndex = Array.IndexOf(args, "--temp-dir");
+                    string? tempDir = tempDirIndex >= 0 && tempDirIndex + 1 < args.Length ? args[tempDirIndex + 1] : null;
                     
-                    string scanResult = await ScanningHelper.ScanToImages(deviceId, driver, dpi, paperSource);
+                    string scanResult = await ScanningHelper.ScanToImages(deviceId, driver, dpi, paperSource, tempDir);
                     Console.WriteLine(scanResult);
                     return 0;
                     
+                case "sources":
+                    if (args.Length < 2)
+                    {
+                        Console.WriteLine("Missing device ID");
+                        return 1;
+                    }
+                    
+                    string sourcesResult = await ScanningHelper.GetSources(args[1], args.Length > 2 ? args[2] : string.Empty);
+                    Console.WriteLine(sourcesResult);
+                    return 0;
+                    
                 default:
                     Console.WriteLine($"Unknown scan subcommand: {subCommand}");
                     return 1;
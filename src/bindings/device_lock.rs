@@ -0,0 +1,94 @@
+//! Cross-process reservation of a scanner by device ID
+//!
+//! Each device gets a lockfile in a shared directory, held with an OS advisory
+//! lock for the length of a scan. The OS drops the lock if the process dies, so
+//! a crashed scan never leaves a device reserved. The files themselves are left
+//! in place; removing one while another process waits on it would let two
+//! processes hold "the" lock at once.
+
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::bindings::error::Naps2Error;
+use crate::bindings::resolution::crc32;
+
+/// How often a waiting scan retries a device that is reserved elsewhere
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where device lockfiles live and how long to wait for one
+#[derive(Debug, Clone)]
+pub(crate) struct DeviceLock {
+    pub(crate) dir: PathBuf,
+    pub(crate) timeout: Duration,
+}
+
+impl DeviceLock {
+    /// Lockfile for a device: readable, and distinct even when IDs only differ in punctuation
+    fn path(&self, device_id: &str) -> PathBuf {
+        let readable: String = device_id.chars()
+            .take(64)
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        self.dir.join(format!("{}-{:08x}.lock", readable, crc32(device_id.as_bytes())))
+    }
+
+    /// Take the device's lock, waiting up to `timeout` for it
+    pub(crate) fn acquire(&self, device_id: &str) -> Result<DeviceLockGuard> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create device lock directory {:?}", self.dir))?;
+        let path = self.path(device_id);
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(&path)
+            .with_context(|| format!("Failed to open device lock {:?}", path))?;
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(DeviceLockGuard { file }),
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => thread::sleep(POLL_INTERVAL),
+                Err(TryLockError::WouldBlock) => {
+                    return Err(Naps2Error::DeviceBusy(format!(
+                        "{} is reserved by another process (waited {:?})", device_id, self.timeout)).into());
+                }
+                Err(TryLockError::Error(e)) => {
+                    return Err(e).with_context(|| format!("Failed to lock {:?}", path));
+                }
+            }
+        }
+    }
+}
+
+/// A device's lock, held until dropped
+#[derive(Debug)]
+pub(crate) struct DeviceLockGuard {
+    file: File,
+}
+
+impl Drop for DeviceLockGuard {
+    fn drop(&mut self) {
+        // Closing the file would release the lock too; this just makes it explicit
+        let _ = self.file.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_is_held_until_the_guard_is_dropped() {
+        let dir = std::env::temp_dir().join(format!("naps2-device-lock-test-{}", std::process::id()));
+        let lock = DeviceLock { dir: dir.clone(), timeout: Duration::ZERO };
+
+        let guard = lock.acquire("escl:192.168.1.20").unwrap();
+        let error = lock.acquire("escl:192.168.1.20").unwrap_err();
+        assert!(matches!(error.downcast_ref::<Naps2Error>(), Some(Naps2Error::DeviceBusy(_))), "{}", error);
+        // Punctuation-only differences still get their own lock
+        drop(lock.acquire("escl_192.168.1.20").unwrap());
+
+        drop(guard);
+        drop(lock.acquire("escl:192.168.1.20").unwrap());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,111 @@
+//! Editable document model for review before export
+
+use anyhow::Result;
+use crate::bindings::error::Naps2Error;
+use crate::bindings::scan::ScanResult;
+
+/// Quarter-turn rotation of a page, clockwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageRotation {
+    #[default]
+    None,
+    Clockwise90,
+    Rotate180,
+    Counterclockwise90,
+}
+
+impl PageRotation {
+    /// Clockwise rotation in degrees
+    pub fn degrees(&self) -> u16 {
+        match self {
+            PageRotation::None => 0,
+            PageRotation::Clockwise90 => 90,
+            PageRotation::Rotate180 => 180,
+            PageRotation::Counterclockwise90 => 270,
+        }
+    }
+
+    /// The rotation after a further quarter turn clockwise
+    pub fn clockwise(self) -> Self {
+        match self {
+            PageRotation::None => PageRotation::Clockwise90,
+            PageRotation::Clockwise90 => PageRotation::Rotate180,
+            PageRotation::Rotate180 => PageRotation::Counterclockwise90,
+            PageRotation::Counterclockwise90 => PageRotation::None,
+        }
+    }
+
+    /// The rotation after a further quarter turn counterclockwise
+    pub fn counterclockwise(self) -> Self {
+        self.clockwise().clockwise().clockwise()
+    }
+}
+
+/// A page image and the edits to apply when it is exported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentPage {
+    pub path: String,
+    pub rotation: PageRotation,
+}
+
+/// An ordered set of pages whose edits are only baked in on export
+///
+/// Rotating a page only records the change, so interactive review doesn't
+/// re-encode the image each time. `PdfClient::export_document` applies the
+/// rotations once, as PDF page rotation, without touching the image data.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    pages: Vec<DocumentPage>,
+}
+
+impl Document {
+    /// Create a document from image paths, with no edits
+    pub fn from_paths(image_paths: &[String]) -> Self {
+        Self {
+            pages: image_paths.iter()
+                .map(|path| DocumentPage { path: path.clone(), rotation: PageRotation::None })
+                .collect(),
+        }
+    }
+
+    /// Create a document from the pages of a scan
+    pub fn from_scan(scan: &ScanResult) -> Self {
+        Self::from_paths(&scan.image_paths)
+    }
+
+    /// Pages in document order
+    pub fn pages(&self) -> &[DocumentPage] {
+        &self.pages
+    }
+
+    /// Image paths in document order
+    pub fn image_paths(&self) -> Vec<String> {
+        self.pages.iter().map(|page| page.path.clone()).collect()
+    }
+
+    /// Set the rotation of the page at `index`
+    pub fn set_rotation(&mut self, index: usize, rotation: PageRotation) -> Result<()> {
+        self.page_mut(index)?.rotation = rotation;
+        Ok(())
+    }
+
+    /// Turn the page at `index` a further quarter turn clockwise
+    pub fn rotate_clockwise(&mut self, index: usize) -> Result<()> {
+        let page = self.page_mut(index)?;
+        page.rotation = page.rotation.clockwise();
+        Ok(())
+    }
+
+    /// Turn the page at `index` a further quarter turn counterclockwise
+    pub fn rotate_counterclockwise(&mut self, index: usize) -> Result<()> {
+        let page = self.page_mut(index)?;
+        page.rotation = page.rotation.counterclockwise();
+        Ok(())
+    }
+
+    fn page_mut(&mut self, index: usize) -> Result<&mut DocumentPage> {
+        let count = self.pages.len();
+        self.pages.get_mut(index).ok_or_else(|| Naps2Error::InvalidOptionsError(
+            format!("Page index {} is out of range for a {}-page document", index, count)).into())
+    }
+}
@@ -1,6 +1,7 @@
 //! Error types for NAPS2 bindings
 
 use thiserror::Error;
+use crate::bindings::scan::{PaperSource, ScanResult, ScannerDevice};
 
 #[derive(Error, Debug)]
 pub enum Naps2Error {
@@ -13,12 +14,73 @@ pub enum Naps2Error {
     #[error("Device not found: {0}")]
     DeviceNotFoundError(String),
     
+    #[error("No scanning devices found")]
+    NoDevices,
+    
+    #[error("Expected one scanning device but found {}", .0.len())]
+    MultipleDevices(Vec<ScannerDevice>),
+    
+    #[error("Scanner driver error: {0}")]
+    DriverError(String),
+    
+    #[error("TLS handshake with the scanner failed: {0}")]
+    TlsError(String),
+    
+    #[error("No pages were scanned: {0}")]
+    NoPagesScanned(String),
+    
     #[error("Scanning failed: {0}")]
     ScanningError(String),
     
+    #[error("Duplex pass page counts differ: {fronts} fronts, {backs} backs")]
+    DuplexMismatch { fronts: usize, backs: usize },
+    
+    #[error("Paper source {requested:?} is not supported by this device (supports {supported:?})")]
+    UnsupportedPaperSource {
+        requested: PaperSource,
+        supported: Vec<PaperSource>,
+    },
+    
+    #[error("Feature not supported by this helper build: {0}")]
+    UnsupportedFeature(String),
+    
+    #[error("Device is busy: {0}")]
+    DeviceBusy(String),
+    
+    #[error("All {0} pool slots are busy")]
+    PoolExhausted(usize),
+    
+    #[error("Invalid options: {0}")]
+    InvalidOptionsError(String),
+    
+    #[error("Scan interrupted after {} pages: {reason}", partial.image_paths.len())]
+    ScanInterruptedError {
+        reason: String,
+        /// Pages saved before the interruption, which can be resumed from
+        partial: ScanResult,
+    },
+    
+    #[error("Scan exceeds configured size limit: {0}")]
+    ScanTooLargeError(String),
+    
     #[error("PDF operation failed: {0}")]
     PdfError(String),
     
+    #[error("Smallest PDF produced was {size} bytes, over the {max_bytes} byte limit")]
+    PdfTooLarge { size: u64, max_bytes: u64 },
+    
     #[error("OCR operation failed: {0}")]
     OcrError(String),
+    
+    #[error("Operation cancelled: {0}")]
+    Cancelled(String),
+    
+    #[error("OCR language data not installed: {0}")]
+    LanguageMissingError(String),
+    
+    #[error("OCR language data is corrupt: {0}")]
+    LanguageCorruptError(String),
+    
+    #[error("Image operation failed: {0}")]
+    ImageError(String),
 }
@@ -0,0 +1,117 @@
+//! Capability manifest reported by the helper application
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use crate::bindings::error::Naps2Error;
+use crate::bindings::scan::Driver;
+
+/// Optional capability that a helper build may or may not include
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Ocr,
+    PdfA,
+    PdfEncryption,
+    PdfImport,
+    ImageProcessing,
+    /// JPEG 2000 encoding and decoding through OpenJPEG
+    Jpeg2000,
+    /// JBIG2 encoding of bilevel images in exported PDFs
+    Jbig2,
+}
+
+impl Feature {
+    /// Name used for the feature in the helper's manifest
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            Feature::Ocr => "Ocr",
+            Feature::PdfA => "PdfA",
+            Feature::PdfEncryption => "PdfEncryption",
+            Feature::PdfImport => "PdfImport",
+            Feature::ImageProcessing => "ImageProcessing",
+            Feature::Jpeg2000 => "Jpeg2000",
+            Feature::Jbig2 => "Jbig2",
+        }
+    }
+}
+
+/// Capabilities supported by the configured helper build
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct FeatureSet {
+    /// Helper version string
+    #[serde(rename = "Version", default)]
+    pub version: String,
+    /// Names of the scanner drivers compiled into the helper
+    #[serde(rename = "Drivers", default)]
+    pub drivers: Vec<String>,
+    /// Names of the optional features compiled into the helper
+    #[serde(rename = "Features", default)]
+    pub features: Vec<String>,
+}
+
+impl FeatureSet {
+    /// Whether the helper supports a feature
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.features.iter().any(|f| f.eq_ignore_ascii_case(feature.to_string()))
+    }
+    
+    /// Whether the helper was built with a scanner driver
+    pub fn supports_driver(&self, driver: Driver) -> bool {
+        self.drivers.iter().any(|d| d.eq_ignore_ascii_case(driver.to_string()))
+    }
+    
+    /// Return `Naps2Error::UnsupportedFeature` if the helper lacks a feature
+    pub fn require(&self, feature: Feature) -> Result<()> {
+        if self.supports(feature) {
+            Ok(())
+        } else {
+            Err(Naps2Error::UnsupportedFeature(feature.to_string().to_string()).into())
+        }
+    }
+}
+
+/// Outcome of one subsystem check in a self-test
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SubsystemStatus {
+    /// Whether the subsystem is working
+    #[serde(rename = "Ok", default)]
+    pub ok: bool,
+    /// What went wrong, if it isn't
+    #[serde(rename = "Error", default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of the helper's self-test, as returned by `Naps2Client::self_test`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SelfTestReport {
+    /// Helper version string
+    #[serde(rename = "Version", default)]
+    pub version: String,
+    /// Scanner driver stack, e.g. SANE or WIA, could be loaded
+    #[serde(rename = "Scan", default)]
+    pub scan: SubsystemStatus,
+    /// Names of the scanner drivers that loaded successfully
+    #[serde(rename = "Drivers", default)]
+    pub drivers: Vec<String>,
+    /// OCR engine could be initialized
+    #[serde(rename = "Ocr", default)]
+    pub ocr: SubsystemStatus,
+    /// A test PDF could be written
+    #[serde(rename = "Pdf", default)]
+    pub pdf: SubsystemStatus,
+}
+
+impl SelfTestReport {
+    /// Whether every subsystem passed
+    pub fn is_healthy(&self) -> bool {
+        self.scan.ok && self.ocr.ok && self.pdf.ok
+    }
+    
+    /// Names and errors of the subsystems that failed
+    pub fn failures(&self) -> Vec<(&'static str, &str)> {
+        [("scan", &self.scan), ("ocr", &self.ocr), ("pdf", &self.pdf)]
+            .into_iter()
+            .filter(|(_, status)| !status.ok)
+            .map(|(name, status)| (name, status.error.as_deref().unwrap_or("failed")))
+            .collect()
+    }
+}
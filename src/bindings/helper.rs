@@ -0,0 +1,486 @@
+//! Shared execution of the helper application
+//!
+//! The helper exits with 0 on success. Failures use the exit codes below so the
+//! crate can report a typed error; any other non-zero code is reported as
+//! `Naps2Error::HelperExecutionError` with the helper's stderr.
+//!
+//! | Code | Meaning                | Error                              |
+//! |------|------------------------|------------------------------------|
+//! | 1    | Unspecified failure    | `Naps2Error::HelperExecutionError` |
+//! | 2    | Device not found       | `Naps2Error::DeviceNotFoundError`  |
+//! | 3    | No pages were scanned  | `Naps2Error::NoPagesScanned`       |
+//! | 4    | Scanner driver failure | `Naps2Error::DriverError`          |
+//! | 5    | TLS handshake failure  | `Naps2Error::TlsError`             |
+
+use anyhow::{Context, Result};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStderr, ChildStdout, Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use serde::de::DeserializeOwned;
+use crate::bindings::error::Naps2Error;
+
+/// Exit code for a device ID that no driver reports
+pub const EXIT_DEVICE_NOT_FOUND: i32 = 2;
+
+/// Exit code for a scan that completed without producing any pages
+pub const EXIT_NO_PAGES: i32 = 3;
+
+/// Exit code for an error raised by the scanner driver
+pub const EXIT_DRIVER_ERROR: i32 = 4;
+
+/// Exit code for a failed TLS handshake with a network scanner
+pub const EXIT_TLS_ERROR: i32 = 5;
+
+/// Default cap on the stdout or stderr captured from one helper run (64 MiB)
+pub const DEFAULT_OUTPUT_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Largest JSON document the crate will parse from the helper (16 MiB)
+pub const MAX_JSON_BYTES: usize = 16 * 1024 * 1024;
+
+/// Deepest nesting of arrays and objects the crate will parse from the helper
+pub const MAX_JSON_DEPTH: usize = 32;
+
+/// Byte order mark some .NET writers put before UTF-8 output
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Appended to stderr when it was cut off at the output limit
+const TRUNCATED_MARKER: &str = "\n[output truncated]";
+
+static OUTPUT_LIMIT: AtomicUsize = AtomicUsize::new(DEFAULT_OUTPUT_LIMIT);
+
+/// Set the most bytes kept from each of a helper's stdout and stderr
+///
+/// Applies process-wide. Output beyond the limit is read and discarded so the
+/// helper never blocks on a full pipe. Truncated stderr ends with a marker;
+/// truncated stdout can't be parsed and fails with `Naps2Error::HelperOutputError`.
+pub fn set_output_limit(bytes: usize) {
+    OUTPUT_LIMIT.store(bytes, Ordering::Relaxed);
+}
+
+/// The current output limit in bytes
+pub fn output_limit() -> usize {
+    OUTPUT_LIMIT.load(Ordering::Relaxed)
+}
+
+static EXTRA_ARGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Append raw arguments to every helper command the crate runs
+///
+/// Advanced and unsupported: this is an escape hatch for custom helper builds
+/// with flags the crate doesn't model yet. The arguments go after everything
+/// the crate passes, are not validated, and are only as stable as the helper's
+/// own command-line contract. Applies process-wide; pass an empty list to clear.
+pub fn set_extra_args<I, S>(args: I)
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    *EXTRA_ARGS.lock().unwrap_or_else(|e| e.into_inner()) = args.into_iter().map(Into::into).collect();
+}
+
+/// The raw arguments currently appended to every helper command
+pub fn extra_args() -> Vec<String> {
+    EXTRA_ARGS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// A helper process registered with `kill_all`
+struct Running {
+    id: u64,
+    child: Child,
+    temp_dir: Option<PathBuf>,
+    killed: bool,
+}
+
+/// Helper processes the crate has spawned and not yet waited on
+static RUNNING: Mutex<Vec<Running>> = Mutex::new(Vec::new());
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// A panicking caller can't leave the list inconsistent, so ignore poisoning
+fn running() -> MutexGuard<'static, Vec<Running>> {
+    RUNNING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Kill every helper process the crate has spawned and not yet waited on, returning how many were killed
+///
+/// This covers blocking calls, `ScanClient::scan_to_sink`, `ScanClient::scan_stream`
+/// and `OcrClient::install_language`. The calls waiting on them fail with
+/// `Naps2Error::Cancelled`, and the temp directory of any scan that was
+/// interrupted is removed.
+pub fn kill_all() -> usize {
+    kill_matching(|_| true)
+}
+
+/// Kill the registered helpers whose id passes `filter`, as `kill_all` does
+fn kill_matching(filter: impl Fn(u64) -> bool) -> usize {
+    let mut killed = 0;
+    for entry in running().iter_mut().filter(|entry| !entry.killed && filter(entry.id)) {
+        if entry.child.kill().is_ok() {
+            killed += 1;
+        }
+        // Reap the helper so it no longer holds files in its temp directory
+        let _ = entry.child.wait();
+        if let Some(dir) = &entry.temp_dir {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+        entry.killed = true;
+    }
+    killed
+}
+
+/// Context for an error starting the helper, as opposed to one reported by it
+#[derive(Debug)]
+struct SpawnError(String);
+
+impl std::fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Whether `error` came from the helper executable not existing
+#[cfg(any(test, feature = "native"))]
+pub(crate) fn is_missing_helper(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<SpawnError>().is_some()
+        && error.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::NotFound)
+}
+
+/// A spawned helper, registered with `kill_all` until it's waited on or dropped
+///
+/// Dropping it without waiting kills the helper.
+pub(crate) struct TrackedChild {
+    id: u64,
+}
+
+impl TrackedChild {
+    /// Register `child`; `temp_dir` is removed if `kill_all` interrupts it
+    pub(crate) fn new(child: Child, temp_dir: Option<PathBuf>) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        running().push(Running { id, child, temp_dir, killed: false });
+        TrackedChild { id }
+    }
+    
+    /// Spawn `cmd` with piped stdout and stderr and register it
+    pub(crate) fn spawn(cmd: &mut Command, temp_dir: Option<PathBuf>) -> Result<(Self, ChildStdout, ChildStderr)> {
+        let mut child = cmd.stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| SpawnError(format!("Failed to execute helper at {:?}", cmd.get_program())))?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        Ok((TrackedChild::new(child, temp_dir), stdout, stderr))
+    }
+    
+    /// Kill the helper if it's still running
+    pub(crate) fn kill(&self) {
+        if let Some(entry) = running().iter_mut().find(|entry| entry.id == self.id) {
+            let _ = entry.child.kill();
+        }
+    }
+    
+    /// Unregister the helper and wait for it to exit
+    ///
+    /// Fails with `Naps2Error::Cancelled` if `kill_all` stopped it.
+    pub(crate) fn wait(self) -> Result<ExitStatus> {
+        let mut entry = self.take().expect("child is registered until waited on");
+        if entry.killed {
+            return Err(Naps2Error::Cancelled("helper was stopped by shutdown".to_string()).into());
+        }
+        entry.child.wait().context("Failed to wait for helper")
+    }
+    
+    fn take(&self) -> Option<Running> {
+        let mut running = running();
+        let index = running.iter().position(|entry| entry.id == self.id)?;
+        Some(running.swap_remove(index))
+    }
+}
+
+impl Drop for TrackedChild {
+    fn drop(&mut self) {
+        if let Some(mut entry) = self.take() {
+            let _ = entry.child.kill();
+            let _ = entry.child.wait();
+        }
+    }
+}
+
+/// Read up to `limit` bytes, draining the rest; returns whether anything was dropped
+pub(crate) fn read_limited<R: Read>(mut reader: R, limit: usize) -> io::Result<(Vec<u8>, bool)> {
+    let mut buffer = Vec::new();
+    reader.by_ref().take(limit as u64).read_to_end(&mut buffer)?;
+    let dropped = io::copy(&mut reader, &mut io::sink())?;
+    Ok((buffer, dropped > 0))
+}
+
+/// Map a failed helper exit to a typed error
+pub(crate) fn exit_error(code: Option<i32>, stderr: &[u8]) -> Naps2Error {
+    let mut message = String::from_utf8_lossy(stderr).trim().to_string();
+    if message.is_empty() {
+        message = match code {
+            Some(code) => format!("helper exited with code {}", code),
+            None => "helper was terminated".to_string(),
+        };
+    }
+    
+    match code {
+        Some(EXIT_DEVICE_NOT_FOUND) => Naps2Error::DeviceNotFoundError(message),
+        Some(EXIT_NO_PAGES) => Naps2Error::NoPagesScanned(message),
+        Some(EXIT_DRIVER_ERROR) => Naps2Error::DriverError(message),
+        Some(EXIT_TLS_ERROR) => Naps2Error::TlsError(message),
+        _ => Naps2Error::HelperExecutionError(message),
+    }
+}
+
+/// Start building a helper command, optionally run from `working_dir`
+pub(crate) fn command(helper_path: &Path, working_dir: Option<&Path>) -> Command {
+    let mut cmd = Command::new(helper_path);
+    if let Some(dir) = working_dir {
+        cmd.current_dir(dir);
+    }
+    cmd
+}
+
+/// `path` as the helper sees it when run from `working_dir`
+///
+/// Relative paths are joined onto `working_dir`, so checks made in Rust look
+/// at the same file the helper will open.
+pub(crate) fn resolve(working_dir: Option<&Path>, path: &Path) -> PathBuf {
+    match working_dir {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Run a helper command to completion, capturing output up to the output limit
+///
+/// Like `Command::output`, but fails if stdout had to be truncated.
+pub(crate) fn capture(cmd: &mut Command) -> Result<Output> {
+    capture_in(cmd, None)
+}
+
+/// Like `capture`, removing `temp_dir` if `kill_all` interrupts the helper
+pub(crate) fn capture_in(cmd: &mut Command, temp_dir: Option<PathBuf>) -> Result<Output> {
+    let limit = output_limit();
+    cmd.args(extra_args()).stdin(Stdio::null());
+    
+    // Track the child while its output is read so kill_all can reach it
+    let (child, stdout, stderr) = TrackedChild::spawn(cmd, temp_dir)?;
+    
+    // Read stderr on its own thread so neither pipe can fill up and stall the helper
+    let stderr_reader = thread::spawn(move || read_limited(stderr, limit));
+    let stdout_result = read_limited(stdout, limit);
+    let stderr_result = stderr_reader.join();
+    
+    let status = child.wait()?;
+    
+    let (stdout, stdout_truncated) = stdout_result.context("Failed to read helper output")?;
+    let (mut stderr, stderr_truncated) = stderr_result
+        .map_err(|_| Naps2Error::HelperExecutionError("stderr reader panicked".to_string()))?
+        .context("Failed to read helper output")?;
+    
+    if stderr_truncated {
+        stderr.extend_from_slice(TRUNCATED_MARKER.as_bytes());
+    }
+    if stdout_truncated {
+        return Err(Naps2Error::HelperOutputError(format!(
+            "Helper output exceeded the {} byte limit", limit)).into());
+    }
+    
+    Ok(Output { status, stdout, stderr })
+}
+
+/// Run a helper command, returning its output if it exited successfully
+pub(crate) fn run(cmd: &mut Command) -> Result<Output> {
+    run_in(cmd, None)
+}
+
+/// Like `run`, removing `temp_dir` if `kill_all` interrupts the helper
+pub(crate) fn run_in(cmd: &mut Command, temp_dir: Option<PathBuf>) -> Result<Output> {
+    let output = capture_in(cmd, temp_dir)?;
+    
+    if !output.status.success() {
+        return Err(exit_error(output.status.code(), &output.stderr).into());
+    }
+    
+    Ok(output)
+}
+
+/// A fresh directory path for a scan's pages, passed to the helper with `--temp-dir`
+///
+/// Choosing it here lets `kill_all` remove it if the scan is interrupted.
+pub(crate) fn scan_temp_dir() -> PathBuf {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("naps2_scan_{}_{}_{}", std::process::id(), nanos, id))
+}
+
+/// Parse JSON from the helper, rejecting oversized or deeply nested documents
+///
+/// A leading UTF-8 byte order mark and surrounding whitespace are ignored.
+/// The limits are checked on the raw bytes before anything is deserialized,
+/// so a buggy helper can't make the host allocate without bound. Any failure
+/// is a `Naps2Error::HelperOutputError`.
+pub(crate) fn parse_json<T: DeserializeOwned>(json: impl AsRef<[u8]>) -> Result<T> {
+    let json = json.as_ref();
+    let json = json.strip_prefix(UTF8_BOM).unwrap_or(json).trim_ascii();
+    if json.len() > MAX_JSON_BYTES {
+        return Err(Naps2Error::HelperOutputError(format!(
+            "JSON output of {} bytes exceeds the {} byte limit", json.len(), MAX_JSON_BYTES)).into());
+    }
+    
+    // Track nesting outside string literals
+    let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+    for &byte in json {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > MAX_JSON_DEPTH {
+                    return Err(Naps2Error::HelperOutputError(format!(
+                        "JSON output is nested more than {} levels deep", MAX_JSON_DEPTH)).into());
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    
+    serde_json::from_slice(json)
+        .map_err(|e| Naps2Error::HelperOutputError(format!("JSON parse error: {}", e)).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn output_error(error: anyhow::Error) -> String {
+        match error.downcast::<Naps2Error>() {
+            Ok(Naps2Error::HelperOutputError(message)) => message,
+            other => panic!("expected HelperOutputError, got {:?}", other),
+        }
+    }
+
+    /// A JSON string literal of exactly `len` bytes
+    fn json_string(len: usize) -> String {
+        format!("\"{}\"", "a".repeat(len - 2))
+    }
+
+    fn nested(depth: usize) -> String {
+        format!("{}{}", "[".repeat(depth), "]".repeat(depth))
+    }
+
+    #[test]
+    fn json_at_size_limit_parses() {
+        let parsed: String = parse_json(json_string(MAX_JSON_BYTES)).unwrap();
+        assert_eq!(parsed.len(), MAX_JSON_BYTES - 2);
+    }
+
+    #[test]
+    fn json_over_size_limit_is_rejected() {
+        let message = output_error(parse_json::<String>(json_string(MAX_JSON_BYTES + 1)).unwrap_err());
+        assert!(message.contains(&format!("{} bytes exceeds the {} byte limit", MAX_JSON_BYTES + 1, MAX_JSON_BYTES)),
+                "{}", message);
+    }
+
+    #[test]
+    fn json_at_depth_limit_parses() {
+        assert!(parse_json::<Value>(nested(MAX_JSON_DEPTH)).is_ok());
+    }
+
+    #[test]
+    fn json_over_depth_limit_is_rejected() {
+        let message = output_error(parse_json::<Value>(nested(MAX_JSON_DEPTH + 1)).unwrap_err());
+        assert!(message.contains(&format!("nested more than {} levels deep", MAX_JSON_DEPTH)), "{}", message);
+    }
+
+    #[test]
+    fn brackets_inside_strings_do_not_count_toward_depth() {
+        let json = format!("{{\"Text\":\"{}\\\"{}\"}}", "[".repeat(MAX_JSON_DEPTH + 1), "{".repeat(MAX_JSON_DEPTH + 1));
+        let parsed: Value = parse_json(json).unwrap();
+        assert!(parsed["Text"].as_str().unwrap().starts_with('['));
+    }
+
+    #[test]
+    fn relative_paths_resolve_against_the_working_dir() {
+        let dir = Path::new("/data/scans");
+        assert_eq!(resolve(Some(dir), Path::new("page.jpg")), dir.join("page.jpg"));
+        assert_eq!(resolve(Some(dir), Path::new("/tmp/page.jpg")), Path::new("/tmp/page.jpg"));
+        assert_eq!(resolve(None, Path::new("page.jpg")), Path::new("page.jpg"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn killing_cancels_tracked_helpers_and_removes_their_temp_dirs() {
+        let dir = scan_temp_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let (child, _, _) = TrackedChild::spawn(Command::new("sleep").arg("30"), Some(dir.clone())).unwrap();
+        
+        // Other tests' helpers are left alone; kill_all is kill_matching for every id
+        let id = child.id;
+        assert_eq!(kill_matching(|other| other == id), 1);
+        assert!(!dir.exists());
+        match child.wait().unwrap_err().downcast::<Naps2Error>() {
+            Ok(Naps2Error::Cancelled(_)) => {}
+            other => panic!("expected Cancelled, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_helper_is_told_apart_from_other_missing_files() {
+        let error = capture(&mut Command::new("/nonexistent/naps2-helper")).unwrap_err();
+        assert!(is_missing_helper(&error), "{:?}", error);
+        
+        let error = anyhow::Error::new(io::Error::from(io::ErrorKind::NotFound)).context("Failed to open image");
+        assert!(!is_missing_helper(&error));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dropping_a_tracked_helper_unregisters_it() {
+        let (child, _, _) = TrackedChild::spawn(Command::new("sleep").arg("30"), None).unwrap();
+        let id = child.id;
+        drop(child);
+        assert!(running().iter().all(|entry| entry.id != id));
+    }
+
+    #[test]
+    fn leading_byte_order_mark_is_skipped() {
+        let parsed: Value = parse_json("\u{FEFF}{\"Success\":true}").unwrap();
+        assert_eq!(parsed["Success"], true);
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_skipped() {
+        let parsed: Value = parse_json("\r\n  {\"Success\":true}\n").unwrap();
+        assert_eq!(parsed["Success"], true);
+    }
+
+    #[test]
+    fn byte_order_mark_and_whitespace_do_not_count_toward_size_limit() {
+        let json = format!("\u{FEFF}\r\n{}\n", json_string(MAX_JSON_BYTES));
+        assert!(parse_json::<String>(json).is_ok());
+    }
+
+    #[test]
+    fn malformed_json_is_an_output_error() {
+        let message = output_error(parse_json::<Value>("{\"Success\":").unwrap_err());
+        assert!(message.starts_with("JSON parse error"), "{}", message);
+    }
+}
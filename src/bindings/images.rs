@@ -1,7 +1,286 @@
 //! Rust bindings for NAPS2.Images namespace
 
-use anyhow::Result;
-use std::path::Path;
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use crate::bindings::error::Naps2Error;
+use crate::bindings::helper;
+use crate::bindings::pdf::PdfColorSpace;
+use crate::bindings::scan::{check_adjustment, ScanResult};
+
+/// Image file format, identified from file contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Tiff,
+    Bmp,
+    Gif,
+    WebP,
+    /// JPEG 2000, either a JP2 file or a raw codestream
+    ///
+    /// Only available when the helper was built with OpenJPEG, which varies by
+    /// platform and build; check `Feature::Jpeg2000`. Converting to this format
+    /// is lossless; use `ImageClient::convert_jpeg2000` for lossy compression.
+    Jpeg2000,
+    /// HEIC/HEIF, as saved by phone cameras
+    ///
+    /// Accepted as input: PDF export and OCR decode it to PNG first. The helper
+    /// can't write it, so it isn't a valid conversion target.
+    Heif,
+    /// Pick JPEG or a lossless format per image from its content
+    ///
+    /// Only valid as a conversion target; see `AutoFormat` for the heuristic.
+    /// Files are never detected as `Auto`.
+    Auto,
+}
+
+/// Most helpers `ImageClient::thumbnails_for_result` runs at once
+pub const MAX_THUMBNAIL_WORKERS: usize = 8;
+
+/// ISO base media file brands used by HEIC/HEIF images
+const HEIF_BRANDS: [&[u8]; 8] = [b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx", b"mif1", b"msf1"];
+
+impl ImageFormat {
+    /// Identify a format from the leading bytes of a file
+    pub fn from_magic_bytes(bytes: &[u8]) -> Option<ImageFormat> {
+        if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageFormat::Jpeg)
+        } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if bytes.starts_with(b"II*\0") || bytes.starts_with(b"MM\0*") {
+            Some(ImageFormat::Tiff)
+        } else if bytes.starts_with(b"BM") {
+            Some(ImageFormat::Bmp)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some(ImageFormat::Gif)
+        } else if bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP" {
+            Some(ImageFormat::WebP)
+        } else if bytes.starts_with(b"\0\0\0\x0CjP  \r\n\x87\n") || bytes.starts_with(&[0xFF, 0x4F, 0xFF, 0x51]) {
+            Some(ImageFormat::Jpeg2000)
+        } else if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" && HEIF_BRANDS.contains(&&bytes[8..12]) {
+            Some(ImageFormat::Heif)
+        } else {
+            None
+        }
+    }
+    
+    /// Detect the format of an image file from its magic bytes, ignoring the extension
+    pub fn detect<P: AsRef<Path>>(path: P) -> Result<ImageFormat> {
+        let path = path.as_ref();
+        let mut header = Vec::with_capacity(16);
+        File::open(path)
+            .with_context(|| format!("Failed to open image {:?}", path))?
+            .take(16)
+            .read_to_end(&mut header)
+            .with_context(|| format!("Failed to read image {:?}", path))?;
+        
+        Self::from_magic_bytes(&header)
+            .ok_or_else(|| Naps2Error::ImageError(format!("Unrecognized image format: {:?}", path)).into())
+    }
+    
+    /// Identify a format from a file extension, ignoring case
+    ///
+    /// Accepts the common spellings, such as `jpeg` as well as `jpg`. Never
+    /// returns `Auto`.
+    pub fn from_extension(extension: &str) -> Option<ImageFormat> {
+        match extension.to_ascii_lowercase().as_str() {
+            "jpg" | "jpeg" => Some(ImageFormat::Jpeg),
+            "png" => Some(ImageFormat::Png),
+            "tif" | "tiff" => Some(ImageFormat::Tiff),
+            "bmp" => Some(ImageFormat::Bmp),
+            "gif" => Some(ImageFormat::Gif),
+            "webp" => Some(ImageFormat::WebP),
+            "jp2" | "j2k" | "j2c" | "jpf" => Some(ImageFormat::Jpeg2000),
+            "heic" | "heif" => Some(ImageFormat::Heif),
+            _ => None,
+        }
+    }
+    
+    /// Convert to string for passing to the C# helper
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "Jpeg",
+            ImageFormat::Png => "Png",
+            ImageFormat::Tiff => "Tiff",
+            ImageFormat::Bmp => "Bmp",
+            ImageFormat::Gif => "Gif",
+            ImageFormat::WebP => "WebP",
+            ImageFormat::Jpeg2000 => "Jpeg2000",
+            ImageFormat::Heif => "Heif",
+            ImageFormat::Auto => "Auto",
+        }
+    }
+    
+    /// Conventional file extension for the format
+    ///
+    /// `Auto` is resolved to a concrete format before anything is written, so it
+    /// has no extension of its own and returns an empty string.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Tiff => "tiff",
+            ImageFormat::Bmp => "bmp",
+            ImageFormat::Gif => "gif",
+            ImageFormat::WebP => "webp",
+            ImageFormat::Jpeg2000 => "jp2",
+            ImageFormat::Heif => "heic",
+            ImageFormat::Auto => "",
+        }
+    }
+}
+
+/// Compression used by `ImageClient::convert_jpeg2000`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Jpeg2000Mode {
+    /// Reversible wavelet; decodes to exactly the original pixels
+    Lossless,
+    /// Irreversible wavelet targeting a compression ratio, e.g. 20.0 for 20:1
+    Lossy { compression_ratio: f32 },
+}
+
+/// Path to an existing image file in a recognized format
+///
+/// Checked once on construction so an output path or arbitrary string can't
+/// be passed where an input image is expected. Implements `AsRef<Path>`, so it
+/// can be passed to any method taking an image path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImagePath {
+    path: PathBuf,
+    format: ImageFormat,
+}
+
+impl ImagePath {
+    /// Check that `path` is a readable image in a recognized format
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let format = ImageFormat::detect(path)?;
+        Ok(Self { path: path.to_path_buf(), format })
+    }
+    
+    /// Check every path in a list, as returned by scan and import operations
+    pub fn from_all(paths: &[String]) -> Result<Vec<ImagePath>> {
+        paths.iter().map(ImagePath::new).collect()
+    }
+    
+    /// Convert back to the string paths accepted by batch operations
+    pub fn to_strings(images: &[ImagePath]) -> Vec<String> {
+        images.iter().map(|image| image.to_string()).collect()
+    }
+    
+    /// Format detected when the path was checked
+    pub fn format(&self) -> ImageFormat {
+        self.format
+    }
+    
+    pub fn as_path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for ImagePath {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl std::fmt::Display for ImagePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path.to_string_lossy())
+    }
+}
+
+impl TryFrom<&str> for ImagePath {
+    type Error = anyhow::Error;
+    
+    fn try_from(path: &str) -> Result<Self> {
+        Self::new(path)
+    }
+}
+
+impl TryFrom<String> for ImagePath {
+    type Error = anyhow::Error;
+    
+    fn try_from(path: String) -> Result<Self> {
+        Self::new(path)
+    }
+}
+
+impl TryFrom<PathBuf> for ImagePath {
+    type Error = anyhow::Error;
+    
+    fn try_from(path: PathBuf) -> Result<Self> {
+        Self::new(path)
+    }
+}
+
+impl From<ImagePath> for PathBuf {
+    fn from(image: ImagePath) -> Self {
+        image.path
+    }
+}
+
+impl From<ImagePath> for String {
+    fn from(image: ImagePath) -> Self {
+        image.to_string()
+    }
+}
+
+/// An ICC color profile on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IccProfile {
+    path: PathBuf,
+}
+
+impl IccProfile {
+    /// Load an ICC profile, checking that the file has a valid profile header
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut header = Vec::with_capacity(40);
+        File::open(path)
+            .with_context(|| format!("Failed to open ICC profile {:?}", path))?
+            .take(40)
+            .read_to_end(&mut header)
+            .with_context(|| format!("Failed to read ICC profile {:?}", path))?;
+        
+        // Every ICC profile carries the 'acsp' signature at byte 36
+        if header.len() < 40 || &header[36..40] != b"acsp" {
+            return Err(Naps2Error::ImageError(format!("Not an ICC profile: {:?}", path)).into());
+        }
+        
+        Ok(Self { path: path.to_path_buf() })
+    }
+    
+    /// Path of the profile file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Check that every path, relative to `working_dir`, is a readable image in a recognized format
+pub(crate) fn validate_images(image_paths: &[String], working_dir: Option<&Path>) -> Result<()> {
+    for path in image_paths {
+        ImageFormat::detect(helper::resolve(working_dir, Path::new(path)))?;
+    }
+    Ok(())
+}
+
+/// Delete the PNG copies made by `ImageClient::decode_heif_inputs`
+pub(crate) fn remove_decoded(image_paths: &[String], decoded: &[String]) {
+    for (original, decoded) in image_paths.iter().zip(decoded) {
+        if original != decoded {
+            let _ = std::fs::remove_file(decoded);
+        }
+    }
+}
 
 /// Interface for image operations
 pub trait Image {
@@ -53,3 +332,831 @@ impl Image for Naps2Image {
         self.height
     }
 }
+
+/// Thresholding method used by `ImageClient::binarize`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinarizeMethod {
+    /// One global threshold chosen from the histogram; fast, best for evenly lit pages
+    Otsu,
+    /// A threshold per pixel from the mean and deviation of its neighbourhood
+    ///
+    /// Copes with shadows and uneven lighting. `window_size` is the odd side
+    /// length in pixels of the neighbourhood, typically 15-31 at 300 DPI; `k`
+    /// (typically 0.2-0.5) sets how far below the local mean ink must be.
+    Sauvola { window_size: u32, k: f32 },
+}
+
+impl Default for BinarizeMethod {
+    fn default() -> Self {
+        BinarizeMethod::Sauvola { window_size: 25, k: 0.34 }
+    }
+}
+
+/// Chroma subsampling used when encoding JPEG
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChromaSubsampling {
+    /// Full color resolution; largest files, sharpest colored edges
+    Yuv444,
+    /// Color halved horizontally
+    Yuv422,
+    /// Color halved in both directions; smallest files
+    Yuv420,
+}
+
+impl ChromaSubsampling {
+    /// Convert to string for passing to the C# helper
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            ChromaSubsampling::Yuv444 => "444",
+            ChromaSubsampling::Yuv422 => "422",
+            ChromaSubsampling::Yuv420 => "420",
+        }
+    }
+}
+
+/// Color and tone statistics of an image, as measured by the helper
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ImageContent {
+    /// Fraction of pixels (0.0-1.0) with noticeable color, as opposed to gray
+    #[serde(rename = "ColorFraction")]
+    pub color_fraction: f32,
+    /// Fraction of pixels (0.0-1.0) that are close to pure black or pure white
+    #[serde(rename = "BilevelFraction")]
+    pub bilevel_fraction: f32,
+}
+
+/// Rules used to resolve `ImageFormat::Auto`
+///
+/// A page counts as text when `thresholds` classify it as
+/// `ColorContent::Bilevel`; it is then saved in `text_format`, which
+/// compresses clean text far better than JPEG and without artifacts. Anything
+/// else, including grayscale photos, is saved in `photo_format`.
+#[derive(Debug, Clone, Copy)]
+pub struct AutoFormat {
+    pub thresholds: ColorThresholds,
+    pub text_format: ImageFormat,
+    pub photo_format: ImageFormat,
+}
+
+impl Default for AutoFormat {
+    fn default() -> Self {
+        Self {
+            thresholds: ColorThresholds::default(),
+            text_format: ImageFormat::Png,
+            photo_format: ImageFormat::Jpeg,
+        }
+    }
+}
+
+impl AutoFormat {
+    /// The format these rules pick for an image with the given content
+    pub fn choose(&self, content: &ImageContent) -> ImageFormat {
+        if self.thresholds.classify(content) == ColorContent::Bilevel {
+            self.text_format
+        } else {
+            self.photo_format
+        }
+    }
+}
+
+/// How much color an image needs, from `ImageClient::detect_color_content`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorContent {
+    /// Nearly all pixels are close to pure black or pure white
+    Bilevel,
+    /// Effectively no colored pixels, but real shades of gray
+    Grayscale,
+    Color,
+}
+
+impl ColorContent {
+    /// Smallest PDF color space that keeps this content intact
+    pub fn pdf_color_space(&self) -> PdfColorSpace {
+        match self {
+            ColorContent::Bilevel | ColorContent::Grayscale => PdfColorSpace::Grayscale,
+            ColorContent::Color => PdfColorSpace::Preserve,
+        }
+    }
+}
+
+/// Thresholds used to classify an image's `ColorContent`
+///
+/// An image counts as effectively grayscale when no more than
+/// `max_color_fraction` of it is colored, so stray colored pixels from scanner
+/// fringing or a small stamp don't force a color page.
+#[derive(Debug, Clone, Copy)]
+pub struct ColorThresholds {
+    /// Highest `color_fraction` a grayscale or bilevel image may have
+    pub max_color_fraction: f32,
+    /// Lowest `bilevel_fraction` a bilevel image must have
+    pub min_bilevel_fraction: f32,
+}
+
+impl Default for ColorThresholds {
+    fn default() -> Self {
+        Self {
+            max_color_fraction: 0.01,
+            min_bilevel_fraction: 0.9,
+        }
+    }
+}
+
+impl ColorThresholds {
+    /// Classify an image with the given content
+    pub fn classify(&self, content: &ImageContent) -> ColorContent {
+        if content.color_fraction > self.max_color_fraction {
+            ColorContent::Color
+        } else if content.bilevel_fraction >= self.min_bilevel_fraction {
+            ColorContent::Bilevel
+        } else {
+            ColorContent::Grayscale
+        }
+    }
+}
+
+/// A rectangle in image pixels, measured from the top-left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Rect {
+    #[serde(rename = "X")]
+    pub x: u32,
+    #[serde(rename = "Y")]
+    pub y: u32,
+    #[serde(rename = "Width")]
+    pub width: u32,
+    #[serde(rename = "Height")]
+    pub height: u32,
+}
+
+impl Rect {
+    /// Create a rectangle from its top-left corner and size
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self { x, y, width, height }
+    }
+    
+    /// Whether the rectangle is non-empty and lies entirely within an image of the given size
+    pub fn fits_within(&self, image_width: u32, image_height: u32) -> bool {
+        self.width > 0 && self.height > 0
+            && self.x.checked_add(self.width).is_some_and(|right| right <= image_width)
+            && self.y.checked_add(self.height).is_some_and(|bottom| bottom <= image_height)
+    }
+}
+
+/// Outcome of `ImageClient::fix_upside_down`
+#[derive(Debug, Clone, Deserialize)]
+pub struct FlipResult {
+    /// The corrected image, or the original path if no flip was needed
+    #[serde(rename = "Path")]
+    pub path: String,
+    /// Whether the page was upside down and has been rotated 180 degrees
+    #[serde(rename = "Flipped")]
+    pub flipped: bool,
+}
+
+/// A point in image pixels, measured from the top-left corner
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Point {
+    #[serde(rename = "X")]
+    pub x: f32,
+    #[serde(rename = "Y")]
+    pub y: f32,
+}
+
+/// Outcome of `ImageClient::crop_to_document`
+#[derive(Debug, Clone, Deserialize)]
+pub struct DocumentCrop {
+    /// The cropped, perspective-corrected image, or the original path if no document was found
+    #[serde(rename = "Path")]
+    pub path: String,
+    /// Whether page edges were found
+    #[serde(rename = "Found")]
+    pub found: bool,
+    /// Corners of the detected page in the original image: top-left, top-right,
+    /// bottom-right, bottom-left. The image's own corners if nothing was found.
+    #[serde(rename = "Corners")]
+    pub corners: [Point; 4],
+}
+
+/// Pixel dimensions reported by the helper
+#[derive(Debug, Deserialize)]
+struct ImageSize {
+    #[serde(rename = "Width")]
+    width: u32,
+    #[serde(rename = "Height")]
+    height: u32,
+}
+
+/// An opaque RGB color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RgbColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl RgbColor {
+    pub const WHITE: RgbColor = RgbColor { r: 255, g: 255, b: 255 };
+    pub const BLACK: RgbColor = RgbColor { r: 0, g: 0, b: 0 };
+    
+    /// Create a color from its components
+    pub fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+    
+    /// Format as `#RRGGBB` for passing to the C# helper
+    pub fn to_hex(&self) -> String {
+        format!("#{:02X}{:02X}{:02X}", self.r, self.g, self.b)
+    }
+}
+
+impl Default for RgbColor {
+    /// White, the background of a blank page
+    fn default() -> Self {
+        Self::WHITE
+    }
+}
+
+/// Options for unsharp-mask sharpening
+#[derive(Debug, Clone, Copy)]
+pub struct SharpenOptions {
+    /// Strength of the sharpening, as a fraction of the edge contrast to add back
+    pub amount: f32,
+    /// Radius in pixels of the blur used to find edges
+    pub radius: f32,
+    /// Minimum brightness difference (0-255) before a pixel is sharpened
+    pub threshold: u8,
+}
+
+impl Default for SharpenOptions {
+    /// Conservative defaults that crisp up text without visible haloing
+    fn default() -> Self {
+        Self {
+            amount: 0.5,
+            radius: 1.0,
+            threshold: 2,
+        }
+    }
+}
+
+/// How `ImageClient::remove_border` gets rid of a dark frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BorderMode {
+    /// Cut the frame off, making the image smaller
+    #[default]
+    Crop,
+    /// Paint the frame white, keeping the original dimensions
+    Whiten,
+}
+
+impl BorderMode {
+    /// Convert to string for passing to the C# helper
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            BorderMode::Crop => "crop",
+            BorderMode::Whiten => "whiten",
+        }
+    }
+}
+
+/// Options for removing the dark edges left around feeder scans
+#[derive(Debug, Clone, Copy)]
+pub struct BorderOptions {
+    /// Brightest value (0-255) still treated as part of the border
+    pub tolerance: u8,
+    pub mode: BorderMode,
+}
+
+impl Default for BorderOptions {
+    /// Catches the black scanner backing without eating into dark page content
+    fn default() -> Self {
+        Self {
+            tolerance: 64,
+            mode: BorderMode::Crop,
+        }
+    }
+}
+
+/// Optional steps applied by `ImageClient::clean_document`
+#[derive(Debug, Clone, Default)]
+pub struct CleanOptions {
+    /// Remove the dark frame around the page before any other step
+    pub remove_border: Option<BorderOptions>,
+    /// Sharpen the page after the other cleanup steps
+    pub sharpen: Option<SharpenOptions>,
+}
+
+/// Client for image processing operations
+pub struct ImageClient {
+    helper_path: PathBuf,
+    working_dir: Option<PathBuf>,
+}
+
+impl ImageClient {
+    /// Create a new image client with the path to the helper application
+    pub fn new(helper_path: PathBuf) -> Self {
+        Self { helper_path, working_dir: None }
+    }
+    
+    /// Resolve relative paths against `working_dir` and run the helper from there
+    pub fn with_working_dir<P: Into<PathBuf>>(mut self, working_dir: P) -> Self {
+        self.working_dir = Some(working_dir.into());
+        self
+    }
+    
+    /// Start building a helper command
+    fn command(&self) -> Command {
+        helper::command(&self.helper_path, self.working_dir.as_deref())
+    }
+    
+    /// Resolve a relative path against `working_dir`, as the helper would
+    fn resolve(&self, path: &Path) -> PathBuf {
+        helper::resolve(self.working_dir.as_deref(), path)
+    }
+    
+    /// Run the enabled cleanup steps on a scanned page, returning the path of the cleaned image
+    ///
+    /// If no steps are enabled the original path is returned unchanged.
+    pub fn clean_document<P: AsRef<Path>>(&self, image_path: P, options: &CleanOptions) -> Result<String> {
+        let mut path = image_path.as_ref().to_string_lossy().to_string();
+        
+        if let Some(border) = &options.remove_border {
+            path = self.remove_border(&path, border)?;
+        }
+        if let Some(sharpen) = &options.sharpen {
+            path = self.sharpen(&path, sharpen)?;
+        }
+        
+        Ok(path)
+    }
+    
+    /// Sharpen an image with an unsharp mask, returning the path of the new image
+    pub fn sharpen<P: AsRef<Path>>(&self, image_path: P, options: &SharpenOptions) -> Result<String> {
+        if options.amount <= 0.0 || options.radius <= 0.0 {
+            return Err(Naps2Error::ImageError(
+                "Sharpen amount and radius must be greater than zero".to_string()).into());
+        }
+        
+        self.run_image_command("sharpen", image_path.as_ref(), &[
+            options.amount.to_string(),
+            options.radius.to_string(),
+            options.threshold.to_string(),
+        ])
+    }
+    
+    /// Detect the dark frame around a scanned page and crop or whiten it, returning the path of the new image
+    ///
+    /// The frame is found by scanning in from each edge while rows and columns are
+    /// no brighter than `tolerance`, so skewed pages lose their black wedges too.
+    pub fn remove_border<P: AsRef<Path>>(&self, image_path: P, options: &BorderOptions) -> Result<String> {
+        self.run_image_command("remove-border", image_path.as_ref(), &[
+            options.tolerance.to_string(),
+            options.mode.to_string().to_string(),
+        ])
+    }
+    
+    /// Make a small preview of an image, returning its path
+    ///
+    /// The image is scaled down so neither side exceeds `max_dim` pixels,
+    /// keeping its aspect ratio. Smaller images are not enlarged.
+    pub fn thumbnail<P: AsRef<Path>>(&self, image_path: P, max_dim: u32) -> Result<String> {
+        if max_dim == 0 {
+            return Err(Naps2Error::ImageError("Thumbnail size must be greater than zero".to_string()).into());
+        }
+        
+        self.run_image_command("thumbnail", image_path.as_ref(), &[max_dim.to_string()])
+    }
+    
+    /// Make a thumbnail of every page of a scan, in page order
+    ///
+    /// Thumbnails are generated by up to `MAX_THUMBNAIL_WORKERS` helpers at
+    /// once, capped at the number of CPUs. Fails with the first page's error if
+    /// any page fails.
+    pub fn thumbnails_for_result(&self, result: &ScanResult, max_dim: u32) -> Result<Vec<String>> {
+        let pages = &result.image_paths;
+        let workers = thread::available_parallelism().map_or(1, |n| n.get())
+            .min(MAX_THUMBNAIL_WORKERS)
+            .min(pages.len());
+        
+        // Workers claim pages in turn and store each thumbnail at its page index
+        let next = AtomicUsize::new(0);
+        let thumbnails: Mutex<Vec<Option<Result<String>>>> = Mutex::new(pages.iter().map(|_| None).collect());
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(page) = pages.get(index) else { break };
+                    let thumbnail = self.thumbnail(page, max_dim);
+                    thumbnails.lock().unwrap_or_else(|e| e.into_inner())[index] = Some(thumbnail);
+                });
+            }
+        });
+        
+        thumbnails.into_inner().unwrap_or_else(|e| e.into_inner())
+            .into_iter()
+            .map(|thumbnail| thumbnail.unwrap_or_else(|| Err(Naps2Error::ImageError(
+                "Thumbnail worker panicked".to_string()).into())))
+            .collect()
+    }
+    
+    /// Straighten a page that was scanned at a slight angle, returning the path of the new image
+    ///
+    /// The skew angle is estimated from the text lines, as by `detect_skew`;
+    /// pages without a clear angle are copied unchanged.
+    pub fn deskew<P: AsRef<Path>>(&self, image_path: P) -> Result<String> {
+        self.run_image_command("deskew", image_path.as_ref(), &[])
+    }
+    
+    /// Measure how far a page is skewed, in degrees, without changing it
+    ///
+    /// Positive angles are clockwise, and 0.0 means no clear angle was found.
+    /// Uses the same detection as `deskew`, so callers can apply their own
+    /// threshold, e.g. only straightening pages skewed by more than half a degree.
+    pub fn detect_skew<P: AsRef<Path>>(&self, image_path: P) -> Result<f32> {
+        self.run_image_command("skew", image_path.as_ref(), &[])
+    }
+    
+    /// Downscale an image scanned at `source_dpi` to `target_dpi`, returning the path of the new image
+    ///
+    /// The helper resamples with a high-quality filter and records the new
+    /// resolution in the output, so a reduced copy still exports at the right size.
+    pub fn resample<P: AsRef<Path>>(&self, image_path: P, source_dpi: u32, target_dpi: u32) -> Result<String> {
+        if target_dpi == 0 || target_dpi > source_dpi {
+            return Err(Naps2Error::ImageError(format!(
+                "Cannot resample from {} to {} DPI; the target must be between 1 and the source resolution",
+                source_dpi, target_dpi)).into());
+        }
+        
+        self.run_image_command("resample", image_path.as_ref(), &[
+            source_dpi.to_string(),
+            target_dpi.to_string(),
+        ])
+    }
+    
+    /// Convert an image to pure black and white, returning the path of the new image
+    ///
+    /// The result OCRs better than a grayscale scan with uneven lighting and
+    /// compresses to a fraction of the size as a bilevel TIFF.
+    pub fn binarize<P: AsRef<Path>>(&self, image_path: P, method: BinarizeMethod) -> Result<String> {
+        let args = match method {
+            BinarizeMethod::Otsu => vec!["otsu".to_string()],
+            BinarizeMethod::Sauvola { window_size, k } => {
+                if window_size < 3 || window_size % 2 == 0 {
+                    return Err(Naps2Error::ImageError(format!(
+                        "Sauvola window size must be an odd number of at least 3, got {}", window_size)).into());
+                }
+                if !(k > 0.0 && k <= 1.0) {
+                    return Err(Naps2Error::ImageError(format!("Sauvola k must be in (0, 1], got {}", k)).into());
+                }
+                vec!["sauvola".to_string(), window_size.to_string(), k.to_string()]
+            }
+        };
+        
+        self.run_image_command("binarize", image_path.as_ref(), &args)
+    }
+    
+    /// Remove isolated specks of noise, returning the path of the new image
+    ///
+    /// Dust on the glass and the grain of recycled paper leave small dark dots
+    /// that survive binarization and confuse OCR; dots smaller than a text
+    /// stroke are painted with the surrounding background.
+    pub fn despeckle<P: AsRef<Path>>(&self, image_path: P) -> Result<String> {
+        self.run_image_command("despeckle", image_path.as_ref(), &[])
+    }
+    
+    /// Detect a page fed in upside down and rotate it 180 degrees
+    ///
+    /// Uses the OCR engine's orientation detection, but only decides between 0
+    /// and 180 degrees, which is quicker and more reliable than full orientation
+    /// detection for the common feeder mistake. Requires OCR support in the helper.
+    pub fn fix_upside_down<P: AsRef<Path>>(&self, image_path: P) -> Result<FlipResult> {
+        self.run_image_command("fix-upside-down", image_path.as_ref(), &[])
+    }
+    
+    /// Decode one page of a multi-page TIFF to its own image, returning its path
+    ///
+    /// Only that page is loaded, so a large TIFF can be processed a page at a
+    /// time. Combine with `TiffPages` to walk the pages without reading them all.
+    pub fn extract_tiff_page<P: AsRef<Path>>(&self, tiff_path: P, index: usize) -> Result<String> {
+        self.run_image_command("tiff-page", tiff_path.as_ref(), &[index.to_string()])
+    }
+    
+    /// Cut a two-page book spread at the gutter, returning the left and right page paths
+    ///
+    /// `gutter_fraction` is the gutter's position across the image, from 0.0
+    /// (left edge) to 1.0 (right edge); 0.5 is the middle. With `None` the helper
+    /// looks for the gutter as the darkest vertical band near the centre.
+    pub fn split_spread<P: AsRef<Path>>(&self, image_path: P, gutter_fraction: Option<f32>) -> Result<(String, String)> {
+        let gutter = match gutter_fraction {
+            Some(fraction) if fraction > 0.0 && fraction < 1.0 => fraction.to_string(),
+            Some(fraction) => {
+                return Err(Naps2Error::ImageError(format!(
+                    "Gutter fraction must be between 0 and 1, got {}", fraction)).into());
+            }
+            None => "auto".to_string(),
+        };
+        
+        self.run_image_command("split", image_path.as_ref(), &[gutter])
+    }
+    
+    /// Split every spread in a book scan, returning the pages in reading order
+    ///
+    /// The result can be passed straight to `PdfClient::export_pdf`.
+    pub fn split_spreads(&self, image_paths: &[String], gutter_fraction: Option<f32>) -> Result<Vec<String>> {
+        let mut pages = Vec::with_capacity(image_paths.len() * 2);
+        for path in image_paths {
+            let (left, right) = self.split_spread(path, gutter_fraction)?;
+            pages.push(left);
+            pages.push(right);
+        }
+        Ok(pages)
+    }
+    
+    /// Find the region of an image that differs from its background
+    ///
+    /// On a flatbed preview this is the document lying on the glass.
+    pub fn content_bounds<P: AsRef<Path>>(&self, image_path: P) -> Result<Rect> {
+        self.run_image_command("content-bounds", image_path.as_ref(), &[])
+    }
+    
+    /// Crop to a document lying on a larger scan, straightening it if it is slightly rotated
+    ///
+    /// Unlike `content_bounds`, which finds an axis-aligned box around anything
+    /// that isn't background, this looks for the four edges of the page itself
+    /// and maps that quadrilateral to a rectangle, like a phone document scanner.
+    /// `corners` reports what was detected, for checking a bad crop.
+    pub fn crop_to_document<P: AsRef<Path>>(&self, image_path: P) -> Result<DocumentCrop> {
+        self.run_image_command("crop-to-document", image_path.as_ref(), &[])
+    }
+    
+    /// Get the width and height of an image in pixels
+    pub fn dimensions<P: AsRef<Path>>(&self, image_path: P) -> Result<(u32, u32)> {
+        let size: ImageSize = self.run_image_command("size", image_path.as_ref(), &[])?;
+        Ok((size.width, size.height))
+    }
+    
+    /// Crop an image to a rectangle, returning the path of the new image
+    ///
+    /// Fails with `Naps2Error::ImageError` if the rectangle is empty or extends
+    /// past the edges of the image.
+    pub fn crop<P: AsRef<Path>>(&self, image_path: P, rect: &Rect) -> Result<String> {
+        let image_path = image_path.as_ref();
+        let (width, height) = self.dimensions(image_path)?;
+        if !rect.fits_within(width, height) {
+            return Err(Naps2Error::ImageError(format!(
+                "Crop region {}x{} at ({}, {}) is outside the {}x{} image",
+                rect.width, rect.height, rect.x, rect.y, width, height)).into());
+        }
+        
+        self.run_image_command("crop", image_path, &[
+            rect.x.to_string(),
+            rect.y.to_string(),
+            rect.width.to_string(),
+            rect.height.to_string(),
+        ])
+    }
+    
+    /// Measure how colored and how black-and-white an image is
+    pub fn analyze<P: AsRef<Path>>(&self, image_path: P) -> Result<ImageContent> {
+        self.run_image_command("analyze", image_path.as_ref(), &[])
+    }
+    
+    /// Classify an image as bilevel, grayscale or color with the default thresholds
+    ///
+    /// Based on `analyze`. See `detect_color_content_with` to change what counts
+    /// as effectively grayscale.
+    pub fn detect_color_content<P: AsRef<Path>>(&self, image_path: P) -> Result<ColorContent> {
+        self.detect_color_content_with(image_path, &ColorThresholds::default())
+    }
+    
+    /// Classify an image as bilevel, grayscale or color
+    pub fn detect_color_content_with<P: AsRef<Path>>(&self, image_path: P,
+                                                     thresholds: &ColorThresholds) -> Result<ColorContent> {
+        Ok(thresholds.classify(&self.analyze(image_path)?))
+    }
+    
+    /// Convert an image to another format, returning the path of the new image
+    ///
+    /// Images already in the requested format are returned unchanged.
+    /// `ImageFormat::Auto` picks a format with the default `AutoFormat` rules.
+    pub fn convert<P: AsRef<Path>>(&self, image_path: P, format: ImageFormat) -> Result<String> {
+        if format == ImageFormat::Auto {
+            return self.convert_auto(image_path, &AutoFormat::default());
+        }
+        if format == ImageFormat::Heif {
+            return Err(Naps2Error::ImageError("HEIF is only supported as an input format".to_string()).into());
+        }
+        if ImageFormat::detect(self.resolve(image_path.as_ref()))? == format {
+            return Ok(image_path.as_ref().to_string_lossy().to_string());
+        }
+        
+        self.run_image_command("convert", image_path.as_ref(), &[format.to_string().to_string()])
+    }
+    
+    /// Encode an image as JPEG at `quality` (1-100), returning the path of the new image
+    ///
+    /// Unlike `convert`, JPEG inputs are re-encoded rather than returned unchanged.
+    pub fn convert_jpeg<P: AsRef<Path>>(&self, image_path: P, quality: u8) -> Result<String> {
+        if !(1..=100).contains(&quality) {
+            return Err(Naps2Error::ImageError(format!("JPEG quality must be between 1 and 100, got {}", quality)).into());
+        }
+        
+        self.run_image_command("convert", image_path.as_ref(), &[
+            ImageFormat::Jpeg.to_string().to_string(),
+            "--quality".to_string(),
+            quality.to_string(),
+        ])
+    }
+    
+    /// Decode any HEIC/HEIF images to PNG, returning paths to pass on in the same order
+    ///
+    /// Other paths, including unreadable ones, are passed through unchanged for
+    /// the helper to report. Remove the decoded copies
+    /// afterwards with `remove_decoded`.
+    pub(crate) fn decode_heif_inputs(&self, image_paths: &[String]) -> Result<Vec<String>> {
+        image_paths.iter()
+            .map(|path| match ImageFormat::detect(self.resolve(Path::new(path))) {
+                Ok(ImageFormat::Heif) => self.convert(path, ImageFormat::Png),
+                _ => Ok(path.clone()),
+            })
+            .collect()
+    }
+    
+    /// Convert an image to JPEG 2000 (JP2), returning the path of the new image
+    ///
+    /// Requires a helper built with `Feature::Jpeg2000`.
+    pub fn convert_jpeg2000<P: AsRef<Path>>(&self, image_path: P, mode: Jpeg2000Mode) -> Result<String> {
+        let mut args = vec![ImageFormat::Jpeg2000.to_string().to_string()];
+        match mode {
+            Jpeg2000Mode::Lossless => args.push("--lossless".to_string()),
+            Jpeg2000Mode::Lossy { compression_ratio } => {
+                if !(compression_ratio > 1.0 && compression_ratio.is_finite()) {
+                    return Err(Naps2Error::ImageError(format!(
+                        "JPEG 2000 compression ratio must be greater than 1, got {}", compression_ratio)).into());
+                }
+                args.extend(["--rate".to_string(), compression_ratio.to_string()]);
+            }
+        }
+        
+        self.run_image_command("convert", image_path.as_ref(), &args)
+    }
+    
+    /// Convert an image to the format `rules` pick for its content, returning the path of the new image
+    pub fn convert_auto<P: AsRef<Path>>(&self, image_path: P, rules: &AutoFormat) -> Result<String> {
+        let format = rules.choose(&self.analyze(&image_path)?);
+        if format == ImageFormat::Auto {
+            return Err(Naps2Error::ImageError("AutoFormat rules must name concrete formats".to_string()).into());
+        }
+        
+        self.convert(image_path, format)
+    }
+    
+    /// Adjust the brightness and contrast of an already scanned image, returning the path of the new image
+    ///
+    /// Values use the same -1000 to 1000 range as `ScanOptions::brightness` and
+    /// `ScanOptions::contrast`, so a correction can be applied after the fact
+    /// with the settings that would have been used at scan time.
+    pub fn adjust<P: AsRef<Path>>(&self, image_path: P, brightness: i32, contrast: i32) -> Result<String> {
+        check_adjustment("brightness", brightness)?;
+        check_adjustment("contrast", contrast)?;
+        
+        self.run_image_command("adjust", image_path.as_ref(), &[
+            brightness.to_string(),
+            contrast.to_string(),
+        ])
+    }
+    
+    /// Compute a 64-bit difference hash (dHash) of an image
+    ///
+    /// The image is reduced to a 9x8 grayscale grid and each bit records whether a
+    /// pixel is brighter than its right-hand neighbour. Near-identical pages, such
+    /// as the same sheet fed twice, produce hashes a few bits apart.
+    pub fn perceptual_hash<P: AsRef<Path>>(&self, image_path: P) -> Result<u64> {
+        let pixels: Vec<u8> = self.run_image_command("sample", image_path.as_ref(), &[
+            "9".to_string(),
+            "8".to_string(),
+        ])?;
+        if pixels.len() != 72 {
+            return Err(Naps2Error::HelperOutputError(
+                format!("Expected 72 grayscale samples, got {}", pixels.len())).into());
+        }
+        
+        let mut hash = 0u64;
+        for row in pixels.chunks(9) {
+            for pair in row.windows(2) {
+                hash = (hash << 1) | u64::from(pair[0] > pair[1]);
+            }
+        }
+        
+        Ok(hash)
+    }
+    
+    /// Similarity of two images from 0.0 (unrelated) to 1.0 (visually identical)
+    ///
+    /// Pages scoring above roughly 0.9 are likely duplicates. To compare many
+    /// pages, hash each once with `perceptual_hash` and use `hash_similarity`.
+    pub fn similarity<P: AsRef<Path>, Q: AsRef<Path>>(&self, a: P, b: Q) -> Result<f32> {
+        Ok(Self::hash_similarity(self.perceptual_hash(a)?, self.perceptual_hash(b)?))
+    }
+    
+    /// Similarity of two perceptual hashes from 0.0 to 1.0
+    pub fn hash_similarity(a: u64, b: u64) -> f32 {
+        1.0 - (a ^ b).count_ones() as f32 / 64.0
+    }
+    
+    /// Set the resolution recorded in an image without touching its pixels
+    ///
+    /// Use this when a driver tags a scan with the wrong DPI so that PDF export
+    /// sizes the page correctly. JPEG (JFIF and EXIF), PNG and TIFF are supported;
+    /// the file is updated in place. TIFFs must already carry resolution tags.
+    pub fn set_dpi<P: AsRef<Path>>(&self, image_path: P, dpi: u32) -> Result<()> {
+        crate::bindings::resolution::set_dpi(&self.resolve(image_path.as_ref()), dpi)
+    }
+    
+    /// Record a page's position in its document inside the image file
+    ///
+    /// Stored as a JPEG comment or a PNG text field, so the order can be
+    /// recovered with `page_index` after the files have been renamed or
+    /// shuffled. The file is updated in place; only JPEG and PNG are supported.
+    pub fn set_page_index<P: AsRef<Path>>(&self, image_path: P, index: usize) -> Result<()> {
+        crate::bindings::page_index::set_page_index(&self.resolve(image_path.as_ref()), index)
+    }
+    
+    /// Read the page position stored by `set_page_index`, or `None` if there is none
+    pub fn page_index<P: AsRef<Path>>(&self, image_path: P) -> Result<Option<usize>> {
+        crate::bindings::page_index::page_index(&self.resolve(image_path.as_ref()))
+    }
+    
+    /// Rotate an image by an arbitrary angle, returning the path of the new image
+    ///
+    /// Positive angles rotate clockwise. The corners exposed by the rotation are
+    /// painted with `fill_color`. With `expand_canvas` the output grows to fit the
+    /// whole rotated image; otherwise it is cropped back to the original size.
+    pub fn rotate<P: AsRef<Path>>(&self, image_path: P, degrees: f32, fill_color: RgbColor,
+                                  expand_canvas: bool) -> Result<String> {
+        if !degrees.is_finite() {
+            return Err(Naps2Error::ImageError(format!("Invalid rotation angle: {}", degrees)).into());
+        }
+        
+        self.run_image_command("rotate", image_path.as_ref(), &[
+            degrees.to_string(),
+            fill_color.to_hex(),
+            if expand_canvas { "expand" } else { "crop" }.to_string(),
+        ])
+    }
+    
+    /// Run an `image` helper subcommand on one file and parse its JSON output
+    ///
+    /// Most subcommands write a new image and print its path.
+    fn run_image_command<T: DeserializeOwned>(&self, subcommand: &str, image_path: &Path, args: &[String]) -> Result<T> {
+        let image_path = self.resolve(image_path);
+        ImageFormat::detect(&image_path)?;
+        
+        let mut cmd = self.command();
+        cmd.args(["image", subcommand, image_path.to_string_lossy().as_ref()]);
+        cmd.args(args);
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let value: T = helper::parse_json(&stdout)?;
+            
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::resolution::crc32;
+
+    fn png() -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+        for (kind, payload) in [(&b"IHDR"[..], &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0][..]), (b"IEND", &[])] {
+            let mut body = kind.to_vec();
+            body.extend_from_slice(payload);
+            data.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            data.extend_from_slice(&body);
+            data.extend_from_slice(&crc32(&body).to_be_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn relative_paths_resolve_against_the_working_dir() {
+        let dir = std::env::temp_dir().join(format!("naps2-images-working-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("page.png"), png()).unwrap();
+        let images = ImageClient::new(dir.join("missing-helper")).with_working_dir(&dir);
+
+        images.set_page_index("page.png", 4).unwrap();
+        assert_eq!(images.page_index("page.png").unwrap(), Some(4));
+        validate_images(&["page.png".to_string()], Some(&dir)).unwrap();
+        assert!(validate_images(&["page.png".to_string()], None).is_err());
+
+        // The image is found, so the call gets as far as starting the helper
+        let error = images.deskew("page.png").unwrap_err();
+        assert!(error.to_string().contains("Failed to execute helper"), "{}", error);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
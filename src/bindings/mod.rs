@@ -1,20 +1,80 @@
 //! Rust bindings for NAPS2.Sdk
 
+pub mod document;
 pub mod error;
+pub mod features;
+pub mod helper;
+pub mod naming;
 pub mod scan;
 pub mod images;
 pub mod pdf;
 pub mod ocr;
+pub mod pipeline;
+pub mod pool;
+pub mod sink;
+#[cfg(feature = "native")]
+mod native_pdf;
+mod device_lock;
+mod page_index;
+mod recovery;
+mod resolution;
+mod tiff;
 
 /// Re-exports of commonly used types
-pub use scan::{Driver, PaperSource, ScannerDevice, ScanClient};
-pub use pdf::PdfClient;
-pub use ocr::{OcrLanguage, OcrClient};
+pub use scan::{ADJUSTMENT_RANGE, Driver, EsclOptions, PaperSource, ColorMode, PageSize, ScanOptions, ScannerDevice, DeviceInfo, ScanClient, ScanResult, ScanTiming, ScannedPage, DeviceStatus, DeviceEnumeration, DeviceMatcher, FallbackScanResult, DeviceEvent, DeviceWatcher, DocumentType, AutoDetectedScan};
+pub use document::{Document, DocumentPage, PageRotation};
+pub use features::{Feature, FeatureSet, SelfTestReport, SubsystemStatus};
+pub use images::{AutoFormat, BinarizeMethod, BorderMode, BorderOptions, ChromaSubsampling, CleanOptions, ColorContent, ColorThresholds, DocumentCrop, FlipResult, IccProfile, ImageClient, ImageContent, ImageFormat, ImagePath, Jpeg2000Mode, Point, Rect, RgbColor, SharpenOptions, MAX_THUMBNAIL_WORKERS};
+pub use naming::{NamingContext, OutputNaming, OverwriteAction, OverwritePolicy, SavedFile, SequentialNaming, SourceNaming, TemplateNaming, TimestampNaming};
+pub use pdf::{FileSort, ImportedPage, PdfAttachment, PdfBookmarks, PdfBuilder, PdfClient, PdfColorSpace, PdfExportOptions, PdfImageCompression, PdfImageInfo, PdfIssue, PdfMetadata, PdfValidation};
+pub use ocr::{CancellationToken, DownloadProgress, OcrLanguage, OcrClient, OcrOptions, OcrPageResult, PageSeparator, SidecarResult};
+pub use pipeline::{ProcessingPipeline, ProcessingStep, ScanPipeline, MAX_PROCESSING_WORKERS};
+pub use tiff::{TiffPage, TiffPages};
+pub use sink::{FileSink, PageSink};
+pub use pool::{Naps2Pool, PoolMetrics, PoolPolicy, MAX_POOL_SIZE};
 
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use serde::{Deserialize, Serialize};
-use anyhow::{Context, Result};
+use anyhow::Result;
+
+/// Outcome of saving a single image
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FileResult {
+    /// Input image path
+    #[serde(rename = "Source")]
+    pub source: String,
+    /// Path written, if the image was saved
+    #[serde(rename = "Output", default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Why the image couldn't be saved
+    #[serde(rename = "Error", default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Size of the written file in bytes; only reported when saving to a `target_bytes` budget
+    #[serde(rename = "Size", default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// How an existing file at the output's name was handled
+    ///
+    /// For `OverwriteAction::Skipped`, `output` is the existing file that was kept.
+    #[serde(skip)]
+    pub action: Option<OverwriteAction>,
+}
+
+impl FileResult {
+    /// The written path, or the error message if the image failed
+    pub fn result(&self) -> std::result::Result<&str, &str> {
+        match (&self.output, &self.error) {
+            (Some(output), None) => Ok(output),
+            (_, Some(error)) => Err(error),
+            (None, None) => Err("no output reported"),
+        }
+    }
+    
+    /// Whether the image was saved
+    pub fn is_success(&self) -> bool {
+        self.result().is_ok()
+    }
+}
 
 /// Result of a JPEG save operation
 #[derive(Debug, Deserialize, Serialize)]
@@ -29,14 +89,138 @@ pub struct JpegSaveResult {
     pub count: usize,
     #[serde(rename = "Error", skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// Outcome for each input image, in input order
+    #[serde(rename = "Results", default)]
+    pub results: Vec<FileResult>,
+}
+
+impl JpegSaveResult {
+    /// Images that couldn't be saved
+    pub fn failures(&self) -> impl Iterator<Item = &FileResult> {
+        self.results.iter().filter(|r| !r.is_success())
+    }
 }
 
+/// Options for saving images as JPEG files
+#[derive(Debug, Clone, Default)]
+pub struct JpegSaveOptions {
+    /// Color profile to embed in each saved image
+    pub icc_profile: Option<IccProfile>,
+    /// Keep the profile embedded in each source image, if any
+    ///
+    /// Images without a profile fall back to `icc_profile` when it is set.
+    pub preserve_source_profile: bool,
+    /// Name each output after its source, so `report.tiff` is saved as `report.jpg`
+    pub preserve_names: bool,
+    /// What to do when a file with an output's name already exists; `Rename` by default
+    pub overwrite: OverwritePolicy,
+    /// Write progressive JPEGs, which render coarse-to-fine as they download
+    pub progressive: bool,
+    /// Chroma subsampling, or `None` for the helper's default
+    pub chroma_subsampling: Option<ChromaSubsampling>,
+    /// Number of helper processes to convert with at once; 0 or 1 uses a single helper
+    ///
+    /// The images are split into contiguous runs, one per helper, and the
+    /// outputs are numbered and returned in input order as if one helper had
+    /// done the whole batch. Each helper is a separate process, so the speedup
+    /// grows with the worker count until the CPU cores or the disk are busy;
+    /// small batches gain little because every helper pays the start-up cost.
+    pub workers: usize,
+    /// Record each image's zero-based input position in the saved file
+    ///
+    /// See `ImageClient::set_page_index`. Lets the order be recovered after
+    /// the files have been renamed or moved.
+    pub embed_page_index: bool,
+    /// Largest size in bytes for each saved file
+    ///
+    /// Outputs over the budget are re-encoded from their source at the highest
+    /// JPEG quality that fits, found by binary search; the achieved size is
+    /// reported in each `FileResult`. Images that don't fit even at the lowest
+    /// quality are removed and reported as failures.
+    pub target_bytes: Option<u64>,
+    /// Resolution the images were scanned at, allowing `target_bytes` to downsample
+    ///
+    /// When quality alone can't meet the budget, the image is also reduced to
+    /// 3/4, 1/2, 1/3 and then 1/4 of this resolution until it fits.
+    pub downsample_from_dpi: Option<u32>,
+}
+
+/// Result of a ZIP export
+#[cfg(feature = "zip")]
+#[derive(Debug, Clone)]
+pub struct ZipSaveResult {
+    /// Path of the written archive, or the existing archive that was kept if it was skipped
+    pub path: String,
+    /// Number of images in the archive, 0 if it was skipped
+    pub count: usize,
+    pub action: OverwriteAction,
+}
+
+/// A master scan with a reduced-resolution access copy of every page
+#[derive(Debug)]
+pub struct MultiResolutionScan {
+    /// Pages at the resolution they were scanned at
+    pub master: ScanResult,
+    /// Downscaled copies, in the same order as `master.image_paths`
+    pub access_paths: Vec<String>,
+}
+
+/// Options for `Naps2Client::scan_to_archival_pdf`
+#[derive(Debug, Clone)]
+pub struct ArchivalOptions {
+    /// Cleanup applied to every page before export
+    pub clean: CleanOptions,
+    /// OCR language for the searchable text layer, or `None` to skip OCR
+    pub ocr_language: Option<String>,
+    /// Document information embedded in the PDF
+    pub metadata: PdfMetadata,
+    /// Where to write the PDF; defaults to `document.pdf` in the scan's temp directory
+    pub output_path: Option<PathBuf>,
+}
+
+impl Default for ArchivalOptions {
+    fn default() -> Self {
+        Self {
+            clean: CleanOptions::default(),
+            ocr_language: Some("eng".to_string()),
+            metadata: PdfMetadata::default(),
+            output_path: None,
+        }
+    }
+}
+
+/// A PDF small enough to attach to an email, from `Naps2Client::scan_to_emailable_pdf`
+#[derive(Debug, Clone)]
+pub struct EmailablePdf {
+    /// Path of the PDF
+    pub path: String,
+    /// Size of the PDF in bytes
+    pub size: u64,
+    /// Resolution images were downsampled to, or `None` if they were kept as scanned
+    pub image_dpi: Option<u32>,
+    /// Encoding images were embedded with
+    pub image_compression: PdfImageCompression,
+}
+
+/// Image settings tried in turn by `scan_to_emailable_pdf`, largest output first
+const EMAIL_SIZE_STEPS: &[(Option<u32>, PdfImageCompression)] = &[
+    (None, PdfImageCompression::Preserve),
+    (Some(200), PdfImageCompression::Jpeg { quality: 75 }),
+    (Some(150), PdfImageCompression::Jpeg { quality: 60 }),
+    (Some(150), PdfImageCompression::Jpeg { quality: 40 }),
+    (Some(100), PdfImageCompression::Jpeg { quality: 40 }),
+    (Some(72), PdfImageCompression::Jpeg { quality: 30 }),
+];
+
 /// Main client for NAPS2.Sdk
 pub struct Naps2Client {
     helper_path: PathBuf,
+    working_dir: Option<PathBuf>,
     scan_client: ScanClient,
+    image_client: ImageClient,
     pdf_client: PdfClient,
     ocr_client: OcrClient,
+    features: OnceLock<FeatureSet>,
 }
 
 impl Naps2Client {
@@ -44,17 +228,80 @@ impl Naps2Client {
     pub fn new(helper_path: PathBuf) -> Self {
         Self {
             helper_path: helper_path.clone(),
+            working_dir: None,
             scan_client: ScanClient::new(helper_path.clone()),
+            image_client: ImageClient::new(helper_path.clone()),
             pdf_client: PdfClient::new(helper_path.clone()),
             ocr_client: OcrClient::new(helper_path),
+            features: OnceLock::new(),
+        }
+    }
+    
+    /// Run the helper from `working_dir` for every operation
+    ///
+    /// Relative paths passed to the helper, such as output directories, are
+    /// otherwise resolved against whatever directory the calling process was
+    /// started in. Absolute paths are still recommended; the helper path itself
+    /// should always be absolute, as platforms differ in how a relative program
+    /// path combines with a working directory.
+    pub fn with_working_dir<P: Into<PathBuf>>(self, working_dir: P) -> Self {
+        let working_dir = working_dir.into();
+        Self {
+            working_dir: Some(working_dir.clone()),
+            scan_client: self.scan_client.with_working_dir(working_dir.clone()),
+            image_client: self.image_client.with_working_dir(working_dir.clone()),
+            pdf_client: self.pdf_client.with_working_dir(working_dir.clone()),
+            ocr_client: self.ocr_client.with_working_dir(working_dir),
+            ..self
         }
     }
     
+    /// Resolve a relative path against `working_dir`, as the helper would
+    fn resolve(&self, path: &Path) -> PathBuf {
+        helper::resolve(self.working_dir.as_deref(), path)
+    }
+    
+    /// Wait at least `interval` between scans on the same device
+    ///
+    /// See `ScanClient::with_throttle`.
+    pub fn with_scan_throttle(self, interval: std::time::Duration) -> Self {
+        Self {
+            scan_client: self.scan_client.with_throttle(interval),
+            ..self
+        }
+    }
+    
+    /// Reserve each device across processes while scanning
+    ///
+    /// See `ScanClient::with_device_lock`.
+    pub fn with_device_lock(self, timeout: std::time::Duration) -> Self {
+        Self {
+            scan_client: self.scan_client.with_device_lock(timeout),
+            ..self
+        }
+    }
+    
+    /// Cancel in-flight operations by killing every helper process the crate is waiting on
+    ///
+    /// Intended for service shutdown. This is process-wide: operations running
+    /// on other clients or pools are cancelled too, and fail with
+    /// `Naps2Error::Cancelled`. Returns the number of helpers killed. Temp
+    /// directories of interrupted scans are removed; those of completed scans
+    /// belong to the caller and are left alone.
+    pub fn shutdown(&self) -> usize {
+        helper::kill_all()
+    }
+    
     /// Get the scan client
     pub fn scan(&self) -> &ScanClient {
         &self.scan_client
     }
     
+    /// Get the image client
+    pub fn image(&self) -> &ImageClient {
+        &self.image_client
+    }
+    
     /// Get the PDF client
     pub fn pdf(&self) -> &PdfClient {
         &self.pdf_client
@@ -65,31 +312,600 @@ impl Naps2Client {
         &self.ocr_client
     }
     
+    /// Start a fluent scan, clean-up and export chain for a device
+    ///
+    /// See `ScanPipeline`.
+    pub fn pipeline(&self, device_id: &str) -> ScanPipeline<'_> {
+        ScanPipeline::new(self, device_id)
+    }
+    
+    /// Get the capabilities of the helper build
+    ///
+    /// The manifest is queried once and cached for the lifetime of the client.
+    pub fn features(&self) -> Result<FeatureSet> {
+        if let Some(features) = self.features.get() {
+            return Ok(features.clone());
+        }
+        
+        let mut cmd = helper::command(&self.helper_path, self.working_dir.as_deref());
+        cmd.arg("features");
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let features: FeatureSet = helper::parse_json(&stdout)?;
+            
+        Ok(self.features.get_or_init(|| features).clone())
+    }
+    
+    /// Check that the helper can reach its scanner drivers, OCR engine and PDF libraries
+    ///
+    /// Intended as a single startup call that surfaces environment problems, such
+    /// as missing SANE backends or Tesseract data, before any real work is attempted.
+    /// A failing subsystem is reported in the returned report rather than as an error.
+    pub fn self_test(&self) -> Result<SelfTestReport> {
+        let mut cmd = helper::command(&self.helper_path, self.working_dir.as_deref());
+        cmd.arg("selftest");
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let report: SelfTestReport = helper::parse_json(&stdout)?;
+            
+        Ok(report)
+    }
+    
+    /// Scan, clean up, OCR and export a document as PDF/A in one call, returning the PDF path
+    pub fn scan_to_archival_pdf(&self, device_id: &str, scan_options: &ScanOptions,
+                                archival: &ArchivalOptions) -> Result<String> {
+        // Fail before scanning rather than after the pages are on disk
+        let features = self.features()?;
+        features.require(Feature::PdfA)?;
+        if archival.ocr_language.is_some() {
+            features.require(Feature::Ocr)?;
+        }
+        
+        let scan_result = self.scan_client.scan(device_id, scan_options)?;
+        
+        let cleaned = scan_result.image_paths.iter()
+            .map(|path| self.image_client.clean_document(path, &archival.clean))
+            .collect::<Result<Vec<_>>>()?;
+        
+        let output_path = archival.output_path.clone()
+            .unwrap_or_else(|| Path::new(&scan_result.temp_directory).join("document.pdf"));
+        let export_options = PdfExportOptions {
+            pdf_a: true,
+            ocr_language: archival.ocr_language.clone(),
+            metadata: archival.metadata.clone(),
+            ..PdfExportOptions::default()
+        };
+        self.pdf_client.export_pdf_with_options(&output_path, &cleaned, &export_options)?;
+        
+        Ok(output_path.to_string_lossy().to_string())
+    }
+    
+    /// Scan and export a PDF no larger than `max_bytes`, for attaching to an email
+    ///
+    /// The PDF is first exported as scanned, then re-exported with images
+    /// downsampled and recompressed in progressively stronger steps until it
+    /// fits. Steps that would not reduce the resolution below that of the
+    /// sharpest scanned page are skipped. The PDF is written to `document.pdf` in the scan's temp
+    /// directory; if even the smallest step is too large, it is left there and
+    /// `Naps2Error::PdfTooLarge` is returned.
+    pub fn scan_to_emailable_pdf(&self, device_id: &str, scan_options: &ScanOptions,
+                                 max_bytes: u64) -> Result<EmailablePdf> {
+        use anyhow::Context;
+        
+        if max_bytes == 0 {
+            return Err(error::Naps2Error::InvalidOptionsError("max_bytes must be greater than zero".to_string()).into());
+        }
+        
+        let scan_result = self.scan_client.scan(device_id, scan_options)?;
+        let output_path = Path::new(&scan_result.temp_directory).join("document.pdf");
+        
+        // Pages can come back at a different resolution than requested, so
+        // compare against the sharpest one actually scanned
+        let scanned_dpi = scan_result.page_dpis.iter().copied().max().unwrap_or(scan_options.dpi);
+        let mut size = 0;
+        let steps = EMAIL_SIZE_STEPS.iter()
+            .filter(|(dpi, _)| dpi.is_none_or(|dpi| dpi < scanned_dpi));
+        for &(image_dpi, image_compression) in steps {
+            let export_options = PdfExportOptions {
+                image_dpi,
+                image_compression,
+                ..PdfExportOptions::default()
+            };
+            self.pdf_client.export_pdf_with_options(&output_path, &scan_result.image_paths, &export_options)?;
+            
+            size = std::fs::metadata(&output_path)
+                .with_context(|| format!("Failed to read size of {:?}", output_path))?
+                .len();
+            if size <= max_bytes {
+                return Ok(EmailablePdf {
+                    path: output_path.to_string_lossy().to_string(),
+                    size,
+                    image_dpi,
+                    image_compression,
+                });
+            }
+        }
+        
+        Err(error::Naps2Error::PdfTooLarge { size, max_bytes }.into())
+    }
+    
+    /// Scan once at `scan_options.dpi` and derive an access copy of each page at `access_dpi`
+    ///
+    /// This replaces scanning the document twice for archival workflows that
+    /// keep a high-resolution master alongside a smaller copy for everyday use.
+    pub fn scan_multi_resolution(&self, device_id: &str, scan_options: &ScanOptions,
+                                 access_dpi: u32) -> Result<MultiResolutionScan> {
+        if access_dpi == 0 || access_dpi > scan_options.dpi {
+            return Err(error::Naps2Error::InvalidOptionsError(format!(
+                "Access copy DPI {} must be between 1 and the scan DPI {}", access_dpi, scan_options.dpi)).into());
+        }
+        
+        let master = self.scan_client.scan(device_id, scan_options)?;
+        
+        let access_paths = master.image_paths.iter()
+            .map(|path| self.image_client.resample(path, scan_options.dpi, access_dpi))
+            .collect::<Result<Vec<_>>>()?;
+        
+        Ok(MultiResolutionScan { master, access_paths })
+    }
+    
+    /// Package images into a ZIP archive, optionally converting them and encrypting with a password
+    ///
+    /// Entries are named `image_001.<ext>` in input order. When `password` is set,
+    /// each entry is encrypted with AES-256. When `format` is set, images are first
+    /// converted by the helper; otherwise the original files are stored as-is.
+    /// `ImageFormat::Auto` stores photos as JPEG and text pages losslessly.
+    /// An existing archive is replaced.
+    #[cfg(feature = "zip")]
+    pub fn save_images_zip<P: AsRef<Path>>(&self, image_paths: &[String], output_zip: P,
+                                           password: Option<String>, format: Option<ImageFormat>) -> Result<ZipSaveResult> {
+        self.save_images_zip_with_policy(image_paths, output_zip, password, format, OverwritePolicy::Overwrite)
+    }
+    
+    /// Like `save_images_zip`, applying `policy` if the archive already exists
+    ///
+    /// Nothing is converted or written when the archive is skipped.
+    #[cfg(feature = "zip")]
+    pub fn save_images_zip_with_policy<P: AsRef<Path>>(&self, image_paths: &[String], output_zip: P, password: Option<String>,
+                                                       format: Option<ImageFormat>, policy: OverwritePolicy) -> Result<ZipSaveResult> {
+        use anyhow::Context;
+        use std::fs::File;
+        use zip::write::SimpleFileOptions;
+        use zip::{AesMode, CompressionMethod, ZipWriter};
+        
+        images::validate_images(image_paths, self.working_dir.as_deref())?;
+        
+        let intended = self.resolve(output_zip.as_ref());
+        let (target, action) = policy.resolve(intended.clone());
+        let Some(output_zip) = target else {
+            return Ok(ZipSaveResult { path: intended.to_string_lossy().to_string(), count: 0, action });
+        };
+        
+        // Write to a sibling file and rename it into place, so a failure never
+        // destroys an archive that's being overwritten
+        let staging = output_zip.with_file_name(format!(".{}.staging-{}-{}",
+            output_zip.file_name().unwrap_or_default().to_string_lossy(), std::process::id(),
+            std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos()));
+        let written = (|| -> Result<()> {
+            let file = File::create(&staging)
+                .with_context(|| format!("Failed to create archive {:?}", output_zip))?;
+            let mut zip = ZipWriter::new(file);
+            
+            // Scanned images are already compressed, so store them as-is
+            let mut options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+            if let Some(password) = &password {
+                options = options.with_aes_encryption(AesMode::Aes256, password);
+            }
+            
+            for (i, path) in image_paths.iter().enumerate() {
+                // Converted copies are removed once stored, or if storing them fails.
+                // `convert` hands back the input itself when it's already in `format`.
+                let converted = format.map(|format| self.image_client.convert(path, format)).transpose()?;
+                let converted = ConvertedCopy(converted.filter(|converted| converted != path));
+                let source = self.resolve(Path::new(converted.0.as_ref().unwrap_or(path)));
+                let entry_name = format!("image_{:03}.{}", i + 1, ImageFormat::detect(&source)?.extension());
+                
+                zip.start_file(entry_name, options)?;
+                let mut input = File::open(&source)
+                    .with_context(|| format!("Failed to read image {:?}", source))?;
+                std::io::copy(&mut input, &mut zip)?;
+            }
+            
+            zip.finish()?;
+            std::fs::rename(&staging, &output_zip)
+                .with_context(|| format!("Failed to move archive into place at {:?}", output_zip))
+        })();
+        if written.is_err() {
+            let _ = std::fs::remove_file(&staging);
+        }
+        written?;
+        
+        Ok(ZipSaveResult {
+            path: output_zip.to_string_lossy().to_string(),
+            count: image_paths.len(),
+            action,
+        })
+    }
+    
     /// Save images as JPEG files
     pub fn save_as_jpeg(&self, image_paths: &[String], output_dir: &str) -> Result<JpegSaveResult> {
-        let mut cmd = Command::new(&self.helper_path);
+        self.save_as_jpeg_with_options(image_paths, output_dir, &JpegSaveOptions::default())
+    }
+    
+    /// Save images as JPEG files with color profile options
+    pub fn save_as_jpeg_with_options(&self, image_paths: &[String], output_dir: &str,
+                                     options: &JpegSaveOptions) -> Result<JpegSaveResult> {
+        let naming = options.preserve_names.then_some(&SourceNaming as &dyn OutputNaming);
+        self.save_jpeg(image_paths, output_dir, options, naming)
+    }
+    
+    /// Save images as JPEG files, named by `naming` or as the helper numbers them
+    ///
+    /// The helper writes into a staging folder inside `output_dir`, so nothing
+    /// in `output_dir` is touched until each file is moved into place under
+    /// `options.overwrite`.
+    fn save_jpeg(&self, image_paths: &[String], output_dir: &str, options: &JpegSaveOptions,
+                 naming: Option<&dyn OutputNaming>) -> Result<JpegSaveResult> {
+        images::validate_images(image_paths, self.working_dir.as_deref())?;
+        if options.target_bytes == Some(0) {
+            return Err(error::Naps2Error::InvalidOptionsError("target_bytes must be greater than zero".to_string()).into());
+        }
+        if options.downsample_from_dpi == Some(0) {
+            return Err(error::Naps2Error::InvalidOptionsError("downsample_from_dpi must be greater than zero".to_string()).into());
+        }
+        
+        let folder = self.resolve(Path::new(output_dir));
+        let staging = folder.join(format!(".staging-{}-{}", std::process::id(),
+            std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos()));
+        let staged = self.save_jpeg_staged(image_paths, staging.to_string_lossy().as_ref(), options)
+            .and_then(|mut result| {
+                place_outputs(&mut result, image_paths, &folder, naming, options.overwrite)?;
+                Ok(result)
+            });
+        let _ = std::fs::remove_dir_all(&staging);
+        
+        let mut result = staged?;
+        result.directory = output_dir.to_string();
+        Ok(result)
+    }
+    
+    /// Convert and post-process images in `staging_dir`, before they are moved into place
+    fn save_jpeg_staged(&self, image_paths: &[String], staging_dir: &str,
+                        options: &JpegSaveOptions) -> Result<JpegSaveResult> {
+        let mut result = if options.workers > 1 && image_paths.len() > 1 {
+            self.save_jpeg_parallel(image_paths, staging_dir, options)?
+        } else {
+            self.save_jpeg_batch(image_paths, staging_dir, options)?
+        };
+        
+        if let Some(target_bytes) = options.target_bytes {
+            self.fit_to_size(&mut result, image_paths, target_bytes, options.downsample_from_dpi)?;
+        }
+        if options.embed_page_index {
+            embed_page_indexes(&result)?;
+        }
+        
+        Ok(result)
+    }
+    
+    /// Convert images to JPEG with a single helper
+    fn save_jpeg_batch(&self, image_paths: &[String], output_dir: &str,
+                       options: &JpegSaveOptions) -> Result<JpegSaveResult> {
+        let mut cmd = helper::command(&self.helper_path, self.working_dir.as_deref());
         cmd.args(["pdf", "jpeg", output_dir]);
         
+        // Add save options
+        if let Some(profile) = &options.icc_profile {
+            cmd.args(["--icc-profile", profile.path().to_string_lossy().as_ref()]);
+        }
+        if options.preserve_source_profile {
+            cmd.arg("--preserve-icc");
+        }
+        if options.progressive {
+            cmd.arg("--progressive");
+        }
+        if let Some(subsampling) = options.chroma_subsampling {
+            cmd.args(["--chroma-subsampling", subsampling.to_string()]);
+        }
+        
         // Add image paths
         for path in image_paths {
             cmd.arg(path);
         }
         
         // Execute the helper application
-        let output = cmd.output()
-            .with_context(|| format!("Failed to execute helper at {:?}", self.helper_path))?;
-            
-        // Check if the command was successful
-        if !output.status.success() {
-            let error_message = String::from_utf8_lossy(&output.stderr).to_string();
-            return Err(error::Naps2Error::HelperExecutionError(error_message).into());
-        }
+        let output = helper::run(&mut cmd)?;
         
         // Parse the JSON output
         let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let result: JpegSaveResult = serde_json::from_str(&stdout)
-            .map_err(|e| error::Naps2Error::HelperOutputError(format!("JSON parse error: {}", e)))?;
+        let result: JpegSaveResult = helper::parse_json(&stdout)?;
             
         Ok(result)
     }
-}
\ No newline at end of file
+    
+    /// Convert images to JPEG with several helpers at once, keeping input order
+    ///
+    /// Each helper numbers its outputs from `image_001.jpg`, so each writes to
+    /// its own subfolder and the outputs are then moved up and renumbered.
+    fn save_jpeg_parallel(&self, image_paths: &[String], output_dir: &str,
+                          options: &JpegSaveOptions) -> Result<JpegSaveResult> {
+        use anyhow::Context;
+        
+        let workers = options.workers.min(image_paths.len());
+        let chunk_size = image_paths.len().div_ceil(workers);
+        let parts: Vec<(PathBuf, &[String])> = image_paths.chunks(chunk_size).enumerate()
+            .map(|(i, chunk)| (Path::new(output_dir).join(format!(".part-{}", i)), chunk))
+            .collect();
+        
+        let results: Vec<Result<JpegSaveResult>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = parts.iter()
+                .map(|(dir, chunk)| scope.spawn(move || {
+                    self.save_jpeg_batch(chunk, dir.to_string_lossy().as_ref(), options)
+                }))
+                .collect();
+            handles.into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|_| Err(error::Naps2Error::HelperExecutionError(
+                    "JPEG worker panicked".to_string()).into())))
+                .collect()
+        });
+        
+        // Move the outputs up in input order, numbered as a single helper would
+        let mut combined = JpegSaveResult {
+            success: true,
+            directory: output_dir.to_string(),
+            files: Vec::new(),
+            count: 0,
+            error: None,
+            results: Vec::new(),
+        };
+        let mut failure = None;
+        for ((dir, _), result) in parts.iter().zip(results) {
+            let mut result = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    failure.get_or_insert(e);
+                    continue;
+                }
+            };
+            
+            for file in &mut result.files {
+                let target = Path::new(output_dir).join(format!("image_{:03}.jpg", combined.files.len() + 1));
+                std::fs::rename(&*file, &target)
+                    .with_context(|| format!("Failed to move {:?} to {:?}", file, target))?;
+                let target = target.to_string_lossy().to_string();
+                for entry in &mut result.results {
+                    if entry.output.as_deref() == Some(file.as_str()) {
+                        entry.output = Some(target.clone());
+                    }
+                }
+                combined.files.push(target.clone());
+                *file = target;
+            }
+            let _ = std::fs::remove_dir(dir);
+            
+            combined.success &= result.success;
+            if combined.error.is_none() {
+                combined.error = result.error;
+            }
+            combined.results.extend(result.results);
+        }
+        
+        if let Some(e) = failure {
+            return Err(e);
+        }
+        combined.count = combined.files.len();
+        Ok(combined)
+    }
+    
+    /// Re-encode saved JPEGs that are over `target_bytes` so they fit, recording each file's size
+    fn fit_to_size(&self, result: &mut JpegSaveResult, image_paths: &[String], target_bytes: u64,
+                   downsample_from_dpi: Option<u32>) -> Result<()> {
+        use anyhow::Context;
+        
+        fill_file_results(result, image_paths);
+        
+        for file in &mut result.results {
+            let Some(output) = file.output.clone() else { continue };
+            let size = std::fs::metadata(&output)
+                .with_context(|| format!("Failed to read size of {:?}", output))?
+                .len();
+            if size <= target_bytes {
+                file.size = Some(size);
+                continue;
+            }
+            
+            match self.fit_image(&file.source, target_bytes, downsample_from_dpi)? {
+                Some((fitted, size)) => {
+                    std::fs::copy(&fitted, &output)
+                        .with_context(|| format!("Failed to copy {:?} to {:?}", fitted, output))?;
+                    let _ = std::fs::remove_file(&fitted);
+                    file.size = Some(size);
+                }
+                None => {
+                    let _ = std::fs::remove_file(&output);
+                    result.files.retain(|f| *f != output);
+                    file.output = None;
+                    file.error = Some(format!("Could not get {} under {} bytes", file.source, target_bytes));
+                    result.success = false;
+                }
+            }
+        }
+        
+        result.count = result.files.len();
+        Ok(())
+    }
+    
+    /// Find the largest JPEG of `source` no bigger than `target_bytes`, returning its path and size
+    ///
+    /// Tries the full resolution first, then each downsampling step if allowed.
+    fn fit_image(&self, source: &str, target_bytes: u64, downsample_from_dpi: Option<u32>) -> Result<Option<(String, u64)>> {
+        // Each step is `None` for full resolution or `(from, to)` DPI
+        let mut steps = vec![None];
+        if let Some(dpi) = downsample_from_dpi {
+            steps.extend([dpi * 3 / 4, dpi / 2, dpi / 3, dpi / 4].into_iter().filter(|&d| d > 0).map(|d| Some((dpi, d))));
+        }
+        
+        for step in steps {
+            let input = match step {
+                Some((from, to)) => self.image_client.resample(source, from, to)?,
+                None => source.to_string(),
+            };
+            let best = self.best_jpeg_quality(&input, target_bytes);
+            if step.is_some() {
+                let _ = std::fs::remove_file(&input);
+            }
+            if let Some(best) = best? {
+                return Ok(Some(best));
+            }
+        }
+        
+        Ok(None)
+    }
+    
+    /// Binary search for the highest JPEG quality of `input` that fits in `target_bytes`
+    fn best_jpeg_quality(&self, input: &str, target_bytes: u64) -> Result<Option<(String, u64)>> {
+        use anyhow::Context;
+        
+        let (mut low, mut high) = (1u8, 100u8);
+        let mut best: Option<(String, u64)> = None;
+        while low <= high {
+            let quality = low + (high - low) / 2;
+            let candidate = self.image_client.convert_jpeg(input, quality)?;
+            let size = std::fs::metadata(&candidate)
+                .with_context(|| format!("Failed to read size of {:?}", candidate))?
+                .len();
+            
+            if size <= target_bytes {
+                if let Some((previous, _)) = best.replace((candidate, size)) {
+                    let _ = std::fs::remove_file(previous);
+                }
+                low = quality + 1;
+            } else {
+                let _ = std::fs::remove_file(&candidate);
+                high = quality - 1;
+            }
+        }
+        
+        Ok(best)
+    }
+    
+    /// Save images as JPEG files, naming each output with a naming strategy
+    ///
+    /// The strategy takes precedence over `options.preserve_names`. Names that
+    /// already exist are handled by `options.overwrite`.
+    pub fn save_as_jpeg_named(&self, image_paths: &[String], output_dir: &str, options: &JpegSaveOptions,
+                              naming: &dyn OutputNaming) -> Result<JpegSaveResult> {
+        self.save_jpeg(image_paths, output_dir, options, Some(naming))
+    }
+}
+
+/// A converted copy of an input image, deleted when dropped
+#[cfg(feature = "zip")]
+struct ConvertedCopy(Option<String>);
+
+#[cfg(feature = "zip")]
+impl Drop for ConvertedCopy {
+    fn drop(&mut self) {
+        if let Some(path) = &self.0 {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Record each saved JPEG's input position in the file
+fn embed_page_indexes(result: &JpegSaveResult) -> Result<()> {
+    // Older helpers only report the written files, in input order
+    if result.results.is_empty() {
+        for (index, output) in result.files.iter().enumerate() {
+            page_index::set_page_index(Path::new(output), index)?;
+        }
+        return Ok(());
+    }
+    
+    for (index, file) in result.results.iter().enumerate() {
+        if let Some(output) = &file.output {
+            page_index::set_page_index(Path::new(output), index)?;
+        }
+    }
+    Ok(())
+}
+
+/// Build per-file results for older helpers, which only report the written files in input order
+fn fill_file_results(result: &mut JpegSaveResult, image_paths: &[String]) {
+    if result.results.is_empty() {
+        result.results = image_paths.iter().zip(&result.files)
+            .map(|(source, output)| FileResult {
+                source: source.clone(),
+                output: Some(output.clone()),
+                error: None,
+                size: None,
+                action: None,
+            })
+            .collect();
+    }
+}
+
+/// Move each staged JPEG into `folder`, named by `naming` or keeping the helper's name
+fn place_outputs(result: &mut JpegSaveResult, image_paths: &[String], folder: &Path,
+                 naming: Option<&dyn OutputNaming>, policy: OverwritePolicy) -> Result<()> {
+    fill_file_results(result, image_paths);
+    
+    let timestamp = pdf::iso8601(std::time::SystemTime::now());
+    let mut files = Vec::new();
+    for (i, file) in result.results.iter_mut().enumerate() {
+        let Some(output) = &file.output else { continue };
+        let output_path = Path::new(output);
+        let stem = match naming {
+            Some(naming) => {
+                let context = NamingContext { source: Some(Path::new(&file.source)), timestamp: &timestamp };
+                naming.name(i, &context)
+            }
+            None => output_path.file_stem().unwrap_or_default().to_string_lossy().to_string(),
+        };
+        
+        let saved = scan::place_file(output_path, folder.join(format!("{}.jpg", stem)), policy)?;
+        if saved.action != OverwriteAction::Skipped {
+            files.push(saved.path.clone());
+        }
+        file.output = Some(saved.path);
+        file.action = Some(saved.action);
+    }
+    
+    result.count = files.len();
+    result.files = files;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "zip"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_zip_export_keeps_the_existing_archive() {
+        let dir = std::env::temp_dir().join(format!("naps2_zip_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("page.png"), b"\x89PNG\r\n\x1a\n\0\0\0\0").unwrap();
+        std::fs::write(dir.join("pages.zip"), b"previous archive").unwrap();
+        
+        // Conversion needs the helper, which doesn't exist, so the export fails part-way
+        let client = Naps2Client::new(dir.join("missing-helper")).with_working_dir(dir.clone());
+        let result = client.save_images_zip(&["page.png".to_string()], "pages.zip", None, Some(ImageFormat::Tiff));
+        
+        assert!(result.is_err());
+        assert_eq!(std::fs::read(dir.join("pages.zip")).unwrap(), b"previous archive");
+        let leftovers: Vec<_> = std::fs::read_dir(&dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.contains("staging"))
+            .collect();
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,209 @@
+//! File naming strategies for saved and scanned pages
+
+use std::path::{Path, PathBuf};
+
+/// What a naming strategy knows about the file being named
+#[derive(Debug, Clone, Copy)]
+pub struct NamingContext<'a> {
+    /// The image the output was made from, when there is one
+    pub source: Option<&'a Path>,
+    /// Start of the batch as an ISO 8601 UTC timestamp, the same for every file
+    pub timestamp: &'a str,
+}
+
+/// Chooses the file name for each output of a batch
+///
+/// `name` returns the file stem only: the extension is added by the caller,
+/// and names that already exist are handled by the operation's
+/// `OverwritePolicy`, so implementations needn't worry about either.
+pub trait OutputNaming {
+    /// Stem for the file at zero-based `index` in the batch
+    fn name(&self, index: usize, context: &NamingContext) -> String;
+}
+
+/// `prefix0001`, `prefix0002`...
+#[derive(Debug, Clone)]
+pub struct SequentialNaming {
+    pub prefix: String,
+    /// Zero-padded width of the page number
+    pub digits: usize,
+}
+
+impl Default for SequentialNaming {
+    fn default() -> Self {
+        Self {
+            prefix: "page-".to_string(),
+            digits: 4,
+        }
+    }
+}
+
+impl OutputNaming for SequentialNaming {
+    fn name(&self, index: usize, _context: &NamingContext) -> String {
+        format!("{}{:0width$}", self.prefix, index + 1, width = self.digits)
+    }
+}
+
+/// `prefix2024-05-01-143000-001`, with the batch's start date and time in UTC
+#[derive(Debug, Clone, Default)]
+pub struct TimestampNaming {
+    pub prefix: String,
+}
+
+impl OutputNaming for TimestampNaming {
+    fn name(&self, index: usize, context: &NamingContext) -> String {
+        expand_template(&format!("{}{{date}}-{{time}}-{{page:03}}", self.prefix), index + 1, context.timestamp)
+    }
+}
+
+/// A template such as `scan-{date}-{page:03}`
+///
+/// Supports `{page}` (1-based), `{page:N}` (zero-padded to N digits), `{date}`
+/// (`YYYY-MM-DD`) and `{time}` (`HHMMSS`), all in UTC.
+#[derive(Debug, Clone)]
+pub struct TemplateNaming(pub String);
+
+impl OutputNaming for TemplateNaming {
+    fn name(&self, index: usize, context: &NamingContext) -> String {
+        expand_template(&self.0, index + 1, context.timestamp)
+    }
+}
+
+/// The source file's own stem, so `report.tiff` is saved as `report.jpg`
+///
+/// Outputs without a source fall back to `page-0001`-style names.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SourceNaming;
+
+impl OutputNaming for SourceNaming {
+    fn name(&self, index: usize, context: &NamingContext) -> String {
+        match context.source.and_then(|source| source.file_stem()) {
+            Some(stem) => stem.to_string_lossy().to_string(),
+            None => SequentialNaming::default().name(index, context),
+        }
+    }
+}
+
+/// Expand a file naming template for one page
+fn expand_template(template: &str, page: usize, timestamp: &str) -> String {
+    let mut name = template
+        .replace("{date}", timestamp.get(..10).unwrap_or_default())
+        .replace("{time}", &timestamp.get(11..19).unwrap_or_default().replace(':', ""))
+        .replace("{page}", &page.to_string());
+
+    while let Some(start) = name.find("{page:") {
+        let Some(len) = name[start..].find('}') else { break };
+        let width = name[start + 6..start + len].parse::<usize>().unwrap_or(0);
+        name.replace_range(start..start + len + 1, &format!("{:0width$}", page, width = width));
+    }
+
+    name
+}
+
+/// What to do when an output file already exists
+///
+/// Honoured by `JpegSaveOptions::overwrite`, `FileSink` and the `_with_policy`
+/// variants: `ScanClient::scan_to_folder_with_policy` and
+/// `scan_single_to_file_with_policy`, `PdfClient::export_pdf_with_policy`,
+/// `Naps2Client::save_images_zip_with_policy` and
+/// `OcrClient::recognize_to_sidecars_with_policy`. `ScanClient::scan_to_folder`
+/// and `scan_to_folder_named` always rename; every other writer replaces an
+/// existing file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverwritePolicy {
+    /// Replace the existing file
+    Overwrite,
+    /// Keep the existing file and discard the new output
+    Skip,
+    /// Write to `name (1).ext`, `name (2).ext`... instead
+    #[default]
+    Rename,
+}
+
+/// What happened to one output under an `OverwritePolicy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverwriteAction {
+    /// Nothing was in the way; the file was written under its intended name
+    Created,
+    /// An existing file was replaced
+    Overwritten,
+    /// An existing file was kept and the output discarded
+    Skipped,
+    /// An existing file was kept and the output written under a suffixed name
+    Renamed,
+}
+
+/// A file produced under an `OverwritePolicy`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedFile {
+    /// Where the output was written, or the existing file that was kept if it was skipped
+    pub path: String,
+    pub action: OverwriteAction,
+}
+
+impl OverwritePolicy {
+    /// Decide where an output intended for `target` goes
+    ///
+    /// Returns the path to write to, or `None` if the output should be discarded.
+    pub(crate) fn resolve(&self, target: PathBuf) -> (Option<PathBuf>, OverwriteAction) {
+        if !target.exists() {
+            return (Some(target), OverwriteAction::Created);
+        }
+        match self {
+            OverwritePolicy::Overwrite => (Some(target), OverwriteAction::Overwritten),
+            OverwritePolicy::Skip => (None, OverwriteAction::Skipped),
+            OverwritePolicy::Rename => {
+                let folder = target.parent().unwrap_or(Path::new(""));
+                let stem = target.file_stem().unwrap_or_default().to_string_lossy();
+                let extension = target.extension().unwrap_or_default().to_string_lossy();
+                (Some(unique_path(folder, &stem, &extension)), OverwriteAction::Renamed)
+            }
+        }
+    }
+}
+
+/// First path of the form `stem.ext`, `stem (1).ext`, `stem (2).ext`... that doesn't exist
+pub(crate) fn unique_path(folder: &Path, stem: &str, extension: &str) -> PathBuf {
+    let mut candidate = folder.join(format!("{}.{}", stem, extension));
+    let mut n = 1;
+    while candidate.exists() {
+        candidate = folder.join(format!("{} ({}).{}", stem, n, extension));
+        n += 1;
+    }
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn template_fields_are_expanded() {
+        assert_eq!(expand_template("scan-{date}-{time}-{page:3}", 7, "2026-10-15T09:21:51Z"),
+                   "scan-2026-10-15-092151-007");
+        assert_eq!(expand_template("{page}", 12, "2026-10-15T09:21:51Z"), "12");
+    }
+
+    #[test]
+    fn policies_resolve_an_existing_file() {
+        let dir = std::env::temp_dir().join(format!("naps2-naming-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let existing = dir.join("scan.pdf");
+        std::fs::write(&existing, b"").unwrap();
+        std::fs::write(dir.join("scan (1).pdf"), b"").unwrap();
+
+        assert_eq!(OverwritePolicy::Overwrite.resolve(existing.clone()), (Some(existing.clone()), OverwriteAction::Overwritten));
+        assert_eq!(OverwritePolicy::Skip.resolve(existing.clone()), (None, OverwriteAction::Skipped));
+        assert_eq!(OverwritePolicy::Rename.resolve(existing.clone()), (Some(dir.join("scan (2).pdf")), OverwriteAction::Renamed));
+        let new = dir.join("new.pdf");
+        assert_eq!(OverwritePolicy::Skip.resolve(new.clone()), (Some(new), OverwriteAction::Created));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn short_timestamp_expands_to_empty_fields() {
+        assert_eq!(expand_template("{date}_{time}_{page}", 1, "2026-10"), "__1");
+        assert_eq!(expand_template("{date}_{time}", 1, "2026-10-15T09"), "2026-10-15_");
+        assert_eq!(expand_template("{date}", 1, ""), "");
+    }
+}
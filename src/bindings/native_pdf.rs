@@ -0,0 +1,384 @@
+//! Pure-Rust PDF export used when the helper is unavailable
+//!
+//! Only JPEG and non-interlaced 8-bit PNG (grayscale or RGB, without alpha)
+//! images are supported. Image data is embedded as-is without re-encoding.
+//! OCR text layers, encryption, metadata and PDF/A output still require the helper.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use crate::bindings::error::Naps2Error;
+use crate::bindings::images::ImageFormat;
+
+/// Resolution assumed when an image doesn't record one
+const FALLBACK_DPI: f64 = 300.0;
+
+/// Image data ready to be embedded as a PDF image XObject
+struct EmbeddedImage {
+    width: u32,
+    height: u32,
+    dpi_x: f64,
+    dpi_y: f64,
+    color_space: &'static str,
+    filter: &'static str,
+    decode_parms: Option<String>,
+    decode: Option<&'static str>,
+    data: Vec<u8>,
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    data.get(pos..pos + 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    data.get(pos..pos + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn unsupported(path: &Path, reason: &str) -> anyhow::Error {
+    Naps2Error::PdfError(format!("Cannot embed {:?} natively: {}", path, reason)).into()
+}
+
+/// Read the dimensions and resolution of a JPEG so it can be embedded with DCTDecode
+fn parse_jpeg(path: &Path, data: Vec<u8>) -> Result<EmbeddedImage> {
+    let mut pos = 2;
+    let mut density = None;
+    let mut adobe = false;
+    
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            return Err(unsupported(path, "malformed JPEG marker"));
+        }
+        let marker = data[pos + 1];
+        
+        // Fill bytes and standalone markers carry no length
+        if marker == 0xFF {
+            pos += 1;
+            continue;
+        }
+        if marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+            pos += 2;
+            continue;
+        }
+        
+        let length = read_u16(&data, pos + 2).ok_or_else(|| unsupported(path, "truncated JPEG"))? as usize;
+        let segment = data.get(pos + 4..pos + 2 + length)
+            .ok_or_else(|| unsupported(path, "truncated JPEG"))?;
+        
+        match marker {
+            // JFIF header with pixel density
+            0xE0 if segment.starts_with(b"JFIF\0") && segment.len() >= 12 => {
+                let units = segment[7];
+                let x = u16::from_be_bytes([segment[8], segment[9]]) as f64;
+                let y = u16::from_be_bytes([segment[10], segment[11]]) as f64;
+                density = match units {
+                    1 if x > 0.0 && y > 0.0 => Some((x, y)),
+                    2 if x > 0.0 && y > 0.0 => Some((x * 2.54, y * 2.54)),
+                    _ => None,
+                };
+            }
+            // Adobe files store CMYK inverted
+            0xEE if segment.starts_with(b"Adobe") => adobe = true,
+            // Start of frame (excluding DHT, JPG and DAC)
+            0xC0..=0xCF if marker != 0xC4 && marker != 0xC8 && marker != 0xCC => {
+                if segment.len() < 6 {
+                    return Err(unsupported(path, "truncated JPEG frame header"));
+                }
+                let height = u16::from_be_bytes([segment[1], segment[2]]) as u32;
+                let width = u16::from_be_bytes([segment[3], segment[4]]) as u32;
+                let (color_space, decode) = match segment[5] {
+                    1 => ("/DeviceGray", None),
+                    3 => ("/DeviceRGB", None),
+                    4 if adobe => ("/DeviceCMYK", Some("[1 0 1 0 1 0 1 0]")),
+                    4 => ("/DeviceCMYK", None),
+                    n => return Err(unsupported(path, &format!("{} color components", n))),
+                };
+                let (dpi_x, dpi_y) = density.unwrap_or((FALLBACK_DPI, FALLBACK_DPI));
+                
+                return Ok(EmbeddedImage {
+                    width,
+                    height,
+                    dpi_x,
+                    dpi_y,
+                    color_space,
+                    filter: "/DCTDecode",
+                    decode_parms: None,
+                    decode,
+                    data,
+                });
+            }
+            // Start of scan reached without a frame header
+            0xDA => break,
+            _ => {}
+        }
+        
+        pos += 2 + length;
+    }
+    
+    Err(unsupported(path, "no JPEG frame header found"))
+}
+
+/// Collect the compressed pixel data of a PNG so it can be embedded with FlateDecode
+fn parse_png(path: &Path, data: &[u8]) -> Result<EmbeddedImage> {
+    let mut pos = 8;
+    let mut header = None;
+    let mut density = None;
+    let mut idat = Vec::new();
+    
+    while pos + 8 <= data.len() {
+        let length = read_u32(data, pos).unwrap_or(0) as usize;
+        let chunk_type = &data[pos + 4..pos + 8];
+        let chunk = data.get(pos + 8..pos + 8 + length)
+            .ok_or_else(|| unsupported(path, "truncated PNG"))?;
+        
+        match chunk_type {
+            b"IHDR" if chunk.len() >= 13 => {
+                header = Some((
+                    u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]),
+                    u32::from_be_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]),
+                    chunk[8],
+                    chunk[9],
+                    chunk[12],
+                ));
+            }
+            // Pixels per metre
+            b"pHYs" if chunk.len() >= 9 && chunk[8] == 1 => {
+                let x = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as f64;
+                let y = u32::from_be_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as f64;
+                if x > 0.0 && y > 0.0 {
+                    density = Some((x * 0.0254, y * 0.0254));
+                }
+            }
+            b"IDAT" => idat.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ => {}
+        }
+        
+        // Skip the chunk data and CRC
+        pos += 12 + length;
+    }
+    
+    let (width, height, bit_depth, color_type, interlace) = header
+        .ok_or_else(|| unsupported(path, "missing PNG header"))?;
+    if bit_depth != 8 || interlace != 0 {
+        return Err(unsupported(path, "only non-interlaced 8-bit PNGs are supported"));
+    }
+    let (color_space, colors) = match color_type {
+        0 => ("/DeviceGray", 1),
+        2 => ("/DeviceRGB", 3),
+        _ => return Err(unsupported(path, "only grayscale and RGB PNGs without alpha are supported")),
+    };
+    let (dpi_x, dpi_y) = density.unwrap_or((FALLBACK_DPI, FALLBACK_DPI));
+    
+    Ok(EmbeddedImage {
+        width,
+        height,
+        dpi_x,
+        dpi_y,
+        color_space,
+        filter: "/FlateDecode",
+        decode_parms: Some(format!(
+            "<< /Predictor 15 /Colors {} /BitsPerComponent 8 /Columns {} >>", colors, width)),
+        decode: None,
+        data: idat,
+    })
+}
+
+fn load_image(path: &Path) -> Result<EmbeddedImage> {
+    let data = fs::read(path).with_context(|| format!("Failed to read image {:?}", path))?;
+    
+    match ImageFormat::from_magic_bytes(&data) {
+        Some(ImageFormat::Jpeg) => parse_jpeg(path, data),
+        Some(ImageFormat::Png) => parse_png(path, &data),
+        _ => Err(unsupported(path, "only JPEG and PNG images are supported")),
+    }
+}
+
+/// Write the images to a PDF with one page per image, sized from each image's resolution
+pub(crate) fn export_pdf<P: AsRef<Path>>(output_path: P, image_paths: &[String]) -> Result<()> {
+    if image_paths.is_empty() {
+        return Err(Naps2Error::PdfError("No images to export".to_string()).into());
+    }
+    
+    let images = image_paths.iter()
+        .map(|p| load_image(Path::new(p)))
+        .collect::<Result<Vec<_>>>()?;
+    
+    // Objects 1 and 2 are the catalog and page tree; each page then uses three
+    // consecutive objects for the page, its content stream and its image
+    let page_ids: Vec<usize> = (0..images.len()).map(|i| 3 + i * 3).collect();
+    let mut pdf: Vec<u8> = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+    let mut offsets = Vec::new();
+    
+    offsets.push(pdf.len());
+    pdf.extend_from_slice(b"1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n");
+    
+    offsets.push(pdf.len());
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    pdf.extend_from_slice(format!("2 0 obj\n<< /Type /Pages /Kids [{}] /Count {} >>\nendobj\n",
+        kids.join(" "), images.len()).as_bytes());
+    
+    for (image, page_id) in images.iter().zip(&page_ids) {
+        let content_id = page_id + 1;
+        let image_id = page_id + 2;
+        let width_pt = image.width as f64 / image.dpi_x * 72.0;
+        let height_pt = image.height as f64 / image.dpi_y * 72.0;
+        
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!(
+            "{} 0 obj\n<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+             /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>\nendobj\n",
+            page_id, width_pt, height_pt, image_id, content_id).as_bytes());
+        
+        let content = format!("q {:.2} 0 0 {:.2} 0 0 cm /Im0 Do Q", width_pt, height_pt);
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n<< /Length {} >>\nstream\n{}\nendstream\nendobj\n",
+            content_id, content.len(), content).as_bytes());
+        
+        let mut dict = format!(
+            "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace {} \
+             /BitsPerComponent 8 /Filter {} /Length {}",
+            image.width, image.height, image.color_space, image.filter, image.data.len());
+        if let Some(parms) = &image.decode_parms {
+            dict.push_str(&format!(" /DecodeParms {}", parms));
+        }
+        if let Some(decode) = image.decode {
+            dict.push_str(&format!(" /Decode {}", decode));
+        }
+        dict.push_str(" >>");
+        
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n{}\nstream\n", image_id, dict).as_bytes());
+        pdf.extend_from_slice(&image.data);
+        pdf.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+    
+    let xref_offset = pdf.len();
+    pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1).as_bytes());
+    for offset in &offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        offsets.len() + 1, xref_offset).as_bytes());
+    
+    fs::write(output_path.as_ref(), pdf)
+        .with_context(|| format!("Failed to write PDF to {:?}", output_path.as_ref()))?;
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![0xFF, marker];
+        data.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+        data.extend_from_slice(payload);
+        data
+    }
+
+    /// A JPEG header with a JFIF density in dots per inch, ending at the start of scan
+    fn jpeg(width: u16, height: u16, components: u8, dpi: (u16, u16)) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8];
+        let mut jfif = b"JFIF\0\x01\x02\x01".to_vec();
+        jfif.extend_from_slice(&dpi.0.to_be_bytes());
+        jfif.extend_from_slice(&dpi.1.to_be_bytes());
+        jfif.extend_from_slice(&[0, 0]);
+        data.extend(segment(0xE0, &jfif));
+        
+        let mut frame = vec![8];
+        frame.extend_from_slice(&height.to_be_bytes());
+        frame.extend_from_slice(&width.to_be_bytes());
+        frame.push(components);
+        data.extend(segment(0xC0, &frame));
+        data.extend(segment(0xDA, &[0]));
+        data
+    }
+
+    fn chunk(kind: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut data = (payload.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(payload);
+        // The CRC isn't checked
+        data.extend_from_slice(&[0; 4]);
+        data
+    }
+
+    /// An 8-bit RGB PNG whose pixel data is split across two IDAT chunks
+    fn png(width: u32, height: u32, pixels_per_metre: u32) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut header = width.to_be_bytes().to_vec();
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        data.extend(chunk(b"IHDR", &header));
+        
+        let mut phys = pixels_per_metre.to_be_bytes().to_vec();
+        phys.extend_from_slice(&pixels_per_metre.to_be_bytes());
+        phys.push(1);
+        data.extend(chunk(b"pHYs", &phys));
+        data.extend(chunk(b"IDAT", b"abc"));
+        data.extend(chunk(b"IDAT", b"def"));
+        data.extend(chunk(b"IEND", &[]));
+        data
+    }
+
+    #[test]
+    fn jpeg_frame_header_gives_size_color_space_and_density() {
+        let image = parse_jpeg(Path::new("page.jpg"), jpeg(640, 480, 3, (200, 150))).unwrap();
+        assert_eq!((image.width, image.height), (640, 480));
+        assert_eq!((image.dpi_x, image.dpi_y), (200.0, 150.0));
+        assert_eq!(image.color_space, "/DeviceRGB");
+        assert_eq!(image.filter, "/DCTDecode");
+        
+        let gray = parse_jpeg(Path::new("page.jpg"), jpeg(10, 10, 1, (0, 0))).unwrap();
+        assert_eq!(gray.color_space, "/DeviceGray");
+        assert_eq!((gray.dpi_x, gray.dpi_y), (FALLBACK_DPI, FALLBACK_DPI));
+    }
+
+    #[test]
+    fn jpeg_without_frame_header_is_rejected() {
+        let mut data = vec![0xFF, 0xD8];
+        data.extend(segment(0xDA, &[0]));
+        assert!(parse_jpeg(Path::new("page.jpg"), data).is_err());
+    }
+
+    #[test]
+    fn png_idat_chunks_are_concatenated() {
+        let data = png(4, 2, 11811);
+        let image = parse_png(Path::new("page.png"), &data).unwrap();
+        assert_eq!((image.width, image.height), (4, 2));
+        assert_eq!(image.data, b"abcdef");
+        assert_eq!(image.color_space, "/DeviceRGB");
+        assert!((image.dpi_x - 300.0).abs() < 0.1, "{}", image.dpi_x);
+        assert!(image.decode_parms.unwrap().contains("/Colors 3 /BitsPerComponent 8 /Columns 4"));
+    }
+
+    #[test]
+    fn xref_offsets_point_at_their_objects() {
+        let dir = std::env::temp_dir().join(format!("naps2_native_pdf_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (jpeg_path, png_path, pdf_path) = (dir.join("page1.jpg"), dir.join("page2.png"), dir.join("out.pdf"));
+        fs::write(&jpeg_path, jpeg(300, 300, 3, (300, 300))).unwrap();
+        fs::write(&png_path, png(4, 2, 11811)).unwrap();
+        
+        let inputs = [jpeg_path.to_string_lossy().to_string(), png_path.to_string_lossy().to_string()];
+        export_pdf(&pdf_path, &inputs).unwrap();
+        let pdf = fs::read(&pdf_path).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        
+        let text = String::from_utf8_lossy(&pdf);
+        let startxref: usize = text.rsplit("startxref\n").next().unwrap()
+            .lines().next().unwrap().parse().unwrap();
+        assert!(pdf[startxref..].starts_with(b"xref\n0 9\n"));
+        
+        // Skip the header lines and the free entry for object 0
+        let entries: Vec<usize> = String::from_utf8_lossy(&pdf[startxref..]).lines().skip(3).take(8)
+            .map(|line| line[..10].parse().unwrap())
+            .collect();
+        for (index, offset) in entries.iter().enumerate() {
+            let expected = format!("{} 0 obj\n", index + 1);
+            assert!(pdf[*offset..].starts_with(expected.as_bytes()), "object {} at {}", index + 1, offset);
+        }
+        assert!(text.contains("/Count 2"));
+    }
+}
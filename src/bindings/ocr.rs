@@ -1,10 +1,19 @@
 //! Rust bindings for NAPS2.Ocr namespace
 
 use anyhow::{Context, Result};
+use std::fs;
+use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 use serde::{Deserialize, Serialize};
 use crate::bindings::error::Naps2Error;
+use crate::bindings::helper;
+use crate::bindings::images::{remove_decoded, ImageClient, Rect};
+use crate::bindings::naming::{OverwriteAction, OverwritePolicy};
 
 /// OCR language
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -13,62 +22,428 @@ pub struct OcrLanguage {
     pub name: String,
 }
 
+/// Recognized text as returned by the helper
+///
+/// The text is wrapped in JSON so non-ASCII characters survive the trip
+/// through stdout regardless of the platform's console encoding.
+#[derive(Debug, Deserialize)]
+struct RecognizeResponse {
+    #[serde(rename = "Text")]
+    text: String,
+    #[serde(rename = "TimedOut", default)]
+    timed_out: bool,
+}
+
+/// Progress of a language data download
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct DownloadProgress {
+    /// Bytes received so far
+    #[serde(rename = "Downloaded")]
+    pub downloaded: u64,
+    /// Size of the download, when the server reports it
+    #[serde(rename = "Total", default)]
+    pub total: Option<u64>,
+}
+
+impl DownloadProgress {
+    /// Completed fraction from 0.0 to 1.0, or `None` if the total is unknown
+    pub fn fraction(&self) -> Option<f32> {
+        match self.total {
+            Some(total) if total > 0 => Some((self.downloaded as f64 / total as f64).min(1.0) as f32),
+            _ => None,
+        }
+    }
+}
+
+/// Shared flag for cancelling a long-running operation from another thread
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Request cancellation; operations using this token stop as soon as they notice
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// How often a running download checks its cancellation token
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Options for OCR of individual pages
+#[derive(Debug, Clone, Default)]
+pub struct OcrOptions {
+    /// Give up on a page after this long, keeping whatever text was recognized
+    ///
+    /// Stops a page of pure noise from stalling a whole batch.
+    pub page_timeout: Option<Duration>,
+}
+
+/// Text recognized on one page
+#[derive(Debug, Clone)]
+pub struct OcrPageResult {
+    pub text: String,
+    /// Recognition hit `OcrOptions::page_timeout`, so `text` may be partial or empty
+    pub timed_out: bool,
+}
+
+/// Outcome of the helper's language data check
+#[derive(Debug, Deserialize)]
+enum LanguageDataStatus {
+    Ok,
+    Missing,
+    Corrupt,
+}
+
+/// Language verification response returned by the helper
+#[derive(Debug, Deserialize)]
+struct VerifyLanguageResponse {
+    #[serde(rename = "Status")]
+    status: LanguageDataStatus,
+    #[serde(rename = "Error", default)]
+    error: Option<String>,
+}
+
+/// How pages are delimited in text combined from several images
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PageSeparator {
+    /// A form feed between pages, which text tools treat as a page break
+    #[default]
+    FormFeed,
+    /// A `--- Page N ---` line before each page
+    PageHeader,
+    /// Arbitrary text between pages
+    Custom(String),
+}
+
+impl PageSeparator {
+    /// Join per-page text in page order
+    fn join(&self, pages: &[String]) -> String {
+        match self {
+            PageSeparator::FormFeed => pages.join("\x0C"),
+            PageSeparator::Custom(separator) => pages.join(separator),
+            PageSeparator::PageHeader => pages.iter().enumerate()
+                .map(|(i, text)| format!("--- Page {} ---\n{}", i + 1, text))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+/// Text files written by `OcrClient::recognize_to_sidecars`
+#[derive(Debug, Clone)]
+pub struct SidecarResult {
+    /// One `page-NNNN.txt` file per image, in input order
+    pub pages: Vec<String>,
+    /// `document.txt` with every page's text, if requested
+    pub combined: Option<String>,
+    /// What happened to each page's file, in the same order as `pages`
+    pub actions: Vec<OverwriteAction>,
+    /// What happened to `document.txt`, if requested
+    pub combined_action: Option<OverwriteAction>,
+}
+
 /// Client for OCR operations
 pub struct OcrClient {
     helper_path: PathBuf,
+    working_dir: Option<PathBuf>,
 }
 
 impl OcrClient {
     /// Create a new OCR client with the path to the helper application
     pub fn new(helper_path: PathBuf) -> Self {
-        Self { helper_path }
+        Self { helper_path, working_dir: None }
+    }
+    
+    /// Resolve relative paths against `working_dir` and run the helper from there
+    pub fn with_working_dir<P: Into<PathBuf>>(mut self, working_dir: P) -> Self {
+        self.working_dir = Some(working_dir.into());
+        self
+    }
+    
+    /// Start building a helper command
+    fn command(&self) -> Command {
+        helper::command(&self.helper_path, self.working_dir.as_deref())
+    }
+    
+    /// Resolve a relative path against `working_dir`, as the helper would
+    fn resolve(&self, path: &Path) -> PathBuf {
+        helper::resolve(self.working_dir.as_deref(), path)
     }
     
     /// Get the list of available OCR languages
     pub fn get_languages(&self) -> Result<Vec<OcrLanguage>> {
-        let mut cmd = Command::new(&self.helper_path);
+        let mut cmd = self.command();
         cmd.args(["ocr", "languages"]);
         
         // Execute the helper application
-        let output = cmd.output()
-            .with_context(|| format!("Failed to execute helper at {:?}", self.helper_path))?;
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let languages: Vec<OcrLanguage> = helper::parse_json(&stdout)?;
             
-        // Check if the command was successful
-        if !output.status.success() {
-            let error_message = String::from_utf8_lossy(&output.stderr).to_string();
-            return Err(Naps2Error::HelperExecutionError(error_message).into());
+        Ok(languages)
+    }
+    
+    /// Download and install the data for an OCR language
+    ///
+    /// `on_progress` is called for each progress event from the helper, with
+    /// `total` unset when the size isn't known. Cancelling `cancel` stops the
+    /// helper even if the download has stalled, and fails with
+    /// `Naps2Error::Cancelled`.
+    pub fn install_language<F: FnMut(DownloadProgress)>(&self, code: &str, mut on_progress: F,
+                                                        cancel: Option<&CancellationToken>) -> Result<()> {
+        let mut cmd = self.command();
+        cmd.args(["ocr", "install", code])
+            .args(helper::extra_args())
+            .stdin(Stdio::null());
+        
+        let (child, stdout, mut stderr) = helper::TrackedChild::spawn(&mut cmd, None)?;
+        let child = Arc::new(child);
+        let finished = Arc::new(AtomicBool::new(false));
+        
+        // Drain stderr in the background so a chatty helper can't block on a full pipe
+        let stderr_reader = thread::spawn(move || {
+            let mut buffer = Vec::new();
+            let _ = std::io::Read::read_to_end(&mut stderr, &mut buffer);
+            buffer
+        });
+        
+        // Watch for cancellation independently of progress events, which stop if the download stalls
+        let watcher = cancel.cloned().map(|token| {
+            let child = child.clone();
+            let finished = finished.clone();
+            thread::spawn(move || {
+                while !finished.load(Ordering::Relaxed) {
+                    if token.is_cancelled() {
+                        child.kill();
+                        break;
+                    }
+                    thread::sleep(CANCEL_POLL_INTERVAL);
+                }
+            })
+        });
+        
+        // Each progress event is a JSON object on its own line
+        let read_result = BufReader::new(stdout).lines().try_for_each(|line| -> Result<()> {
+            let line = line.context("Failed to read helper output")?;
+            if !line.trim().is_empty() {
+                let progress: DownloadProgress = helper::parse_json(&line)?;
+                on_progress(progress);
+            }
+            Ok(())
+        });
+        
+        finished.store(true, Ordering::Relaxed);
+        if let Some(watcher) = watcher {
+            let _ = watcher.join();
+        }
+        if read_result.is_err() {
+            child.kill();
+        }
+        let child = Arc::into_inner(child).expect("watcher has finished");
+        let status = child.wait()?;
+        let stderr = stderr_reader.join().unwrap_or_default();
+        
+        if cancel.is_some_and(|token| token.is_cancelled()) {
+            return Err(Naps2Error::Cancelled(format!("installing OCR language {}", code)).into());
+        }
+        read_result?;
+        if !status.success() {
+            return Err(helper::exit_error(status.code(), &stderr).into());
         }
         
+        Ok(())
+    }
+    
+    /// Check that the data for an OCR language is installed and loadable
+    ///
+    /// The helper initializes the OCR engine with the language, so a partially
+    /// downloaded or damaged data file is reported as
+    /// `Naps2Error::LanguageCorruptError` rather than failing later during recognition.
+    pub fn verify_language(&self, code: &str) -> Result<()> {
+        let mut cmd = self.command();
+        cmd.args(["ocr", "verify", code]);
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
         // Parse the JSON output
         let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let languages: Vec<OcrLanguage> = serde_json::from_str(&stdout)
-            .map_err(|e| Naps2Error::HelperOutputError(format!("JSON parse error: {}", e)))?;
+        let response: VerifyLanguageResponse = helper::parse_json(&stdout)?;
             
-        Ok(languages)
+        match response.status {
+            LanguageDataStatus::Ok => Ok(()),
+            LanguageDataStatus::Missing => Err(Naps2Error::LanguageMissingError(code.to_string()).into()),
+            LanguageDataStatus::Corrupt => {
+                let reason = response.error.unwrap_or_else(|| "failed to load".to_string());
+                Err(Naps2Error::LanguageCorruptError(format!("{}: {}", code, reason)).into())
+            }
+        }
     }
     
     /// Perform OCR on an image
     pub fn recognize<P: AsRef<Path>>(&self, image_path: P, language: &str) -> Result<String> {
-        let mut cmd = Command::new(&self.helper_path);
+        Ok(self.recognize_with_options(image_path, language, &OcrOptions::default())?.text)
+    }
+    
+    /// Perform OCR on an image, reporting whether the page timed out
+    pub fn recognize_with_options<P: AsRef<Path>>(&self, image_path: P, language: &str,
+                                                  options: &OcrOptions) -> Result<OcrPageResult> {
+        let mut images = ImageClient::new(self.helper_path.clone());
+        if let Some(dir) = &self.working_dir {
+            images = images.with_working_dir(dir.clone());
+        }
+        let original = [image_path.as_ref().to_string_lossy().to_string()];
+        let input = images.decode_heif_inputs(&original)?;
+        
+        let mut cmd = self.command();
         cmd.args([
             "ocr", 
             "recognize", 
-            image_path.as_ref().to_string_lossy().as_ref(),
+            &input[0],
             language
         ]);
+        if let Some(timeout) = options.page_timeout {
+            cmd.args(["--timeout-ms", &timeout.as_millis().to_string()]);
+        }
         
         // Execute the helper application
-        let output = cmd.output()
-            .with_context(|| format!("Failed to execute helper at {:?}", self.helper_path))?;
+        let output = helper::run(&mut cmd);
+        remove_decoded(&original, &input);
+        let output = output?;
+        
+        // Parse the JSON output straight from the raw bytes so invalid UTF-8
+        // is reported instead of being silently replaced
+        let response: RecognizeResponse = helper::parse_json(&output.stdout)?;
             
-        // Check if the command was successful
-        if !output.status.success() {
-            let error_message = String::from_utf8_lossy(&output.stderr).to_string();
-            return Err(Naps2Error::HelperExecutionError(error_message).into());
+        Ok(OcrPageResult {
+            text: response.text,
+            timed_out: response.timed_out,
+        })
+    }
+    
+    /// OCR several pages, in order, without letting one slow page hold up the rest
+    ///
+    /// Pages that hit `options.page_timeout` are returned with `timed_out` set
+    /// instead of failing the batch.
+    pub fn recognize_pages(&self, image_paths: &[String], language: &str,
+                           options: &OcrOptions) -> Result<Vec<OcrPageResult>> {
+        image_paths.iter()
+            .map(|path| self.recognize_with_options(path, language, options))
+            .collect()
+    }
+    
+    /// Perform OCR on one rectangular region of an image, such as a form field
+    ///
+    /// The image is cropped to `rect` first, which is faster and more accurate
+    /// than recognizing the whole page. The region must lie within the image.
+    pub fn recognize_region<P: AsRef<Path>>(&self, image_path: P, rect: &Rect, language: &str) -> Result<String> {
+        let mut images = ImageClient::new(self.helper_path.clone());
+        if let Some(dir) = &self.working_dir {
+            images = images.with_working_dir(dir.clone());
+        }
+        
+        let cropped = images.crop(image_path, rect)?;
+        self.recognize(cropped, language)
+    }
+    
+    /// OCR several pages into one text document, in the order given
+    pub fn recognize_document(&self, image_paths: &[String], language: &str,
+                              separator: &PageSeparator) -> Result<String> {
+        let texts = image_paths.iter()
+            .map(|path| self.recognize(path, language))
+            .collect::<Result<Vec<_>>>()?;
+        
+        Ok(separator.join(&texts))
+    }
+    
+    /// OCR a batch of images, writing each page's text to its own file
+    ///
+    /// Files are named `page-0001.txt`, `page-0002.txt`... in `output_dir`. With
+    /// `combined`, a `document.txt` holding all pages separated by form feeds is
+    /// written as well. Existing files are replaced.
+    pub fn recognize_to_sidecars<P: AsRef<Path>>(&self, image_paths: &[String], language: &str,
+                                                 output_dir: P, combined: bool) -> Result<SidecarResult> {
+        self.recognize_to_sidecars_with_policy(image_paths, language, output_dir, combined, OverwritePolicy::Overwrite)
+    }
+    
+    /// Like `recognize_to_sidecars`, applying `policy` to text files that already exist
+    ///
+    /// Skipped files keep their existing path in the result. A page whose file
+    /// is skipped isn't recognized, unless its text is needed for `document.txt`.
+    pub fn recognize_to_sidecars_with_policy<P: AsRef<Path>>(&self, image_paths: &[String], language: &str, output_dir: P,
+                                                             combined: bool, policy: OverwritePolicy) -> Result<SidecarResult> {
+        let output_dir = &self.resolve(output_dir.as_ref());
+        fs::create_dir_all(output_dir)
+            .with_context(|| format!("Failed to create directory {:?}", output_dir))?;
+        
+        let mut pages = Vec::with_capacity(image_paths.len());
+        let mut actions = Vec::with_capacity(image_paths.len());
+        let mut texts = Vec::with_capacity(image_paths.len());
+        for (i, image_path) in image_paths.iter().enumerate() {
+            let intended = output_dir.join(format!("page-{:04}.txt", i + 1));
+            let (target, action) = policy.resolve(intended.clone());
+            if target.is_some() || combined {
+                let text = self.recognize(image_path, language)?;
+                if let Some(path) = &target {
+                    fs::write(path, &text).with_context(|| format!("Failed to write {:?}", path))?;
+                }
+                texts.push(text);
+            }
+            
+            pages.push(target.unwrap_or(intended).to_string_lossy().to_string());
+            actions.push(action);
         }
         
-        // Get the text output
-        let text = String::from_utf8_lossy(&output.stdout).to_string();
-        Ok(text)
+        let (combined, combined_action) = if combined {
+            let intended = output_dir.join("document.txt");
+            let (target, action) = policy.resolve(intended.clone());
+            if let Some(path) = &target {
+                fs::write(path, PageSeparator::FormFeed.join(&texts)).with_context(|| format!("Failed to write {:?}", path))?;
+            }
+            (Some(target.unwrap_or(intended).to_string_lossy().to_string()), Some(action))
+        } else {
+            (None, None)
+        };
+        
+        Ok(SidecarResult { pages, combined, actions, combined_action })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognized_text_keeps_accents_and_cjk() {
+        let stdout = "{\"Text\":\"Café déjà vu — 東京都の文書\"}".as_bytes();
+        let response: RecognizeResponse = helper::parse_json(stdout).unwrap();
+        assert_eq!(response.text, "Café déjà vu — 東京都の文書");
+        assert!(!response.timed_out);
+    }
+
+    #[test]
+    fn recognized_text_decodes_json_escapes() {
+        let stdout = br#"{"Text":"Caf\u00e9 \u6771\u4eac\n","TimedOut":true}"#;
+        let response: RecognizeResponse = helper::parse_json(stdout).unwrap();
+        assert_eq!(response.text, "Café 東京\n");
+        assert!(response.timed_out);
+    }
+
+    #[test]
+    fn invalid_utf8_is_an_error_not_replacement_characters() {
+        // Latin-1 "é", as a console with the wrong code page would emit it
+        let stdout = b"{\"Text\":\"Caf\xE9\"}";
+        let error = helper::parse_json::<RecognizeResponse>(stdout).unwrap_err();
+        assert!(matches!(error.downcast_ref::<Naps2Error>(), Some(Naps2Error::HelperOutputError(_))), "{}", error);
     }
 }
@@ -0,0 +1,259 @@
+//! Page order recorded inside image files
+//!
+//! The index is stored as a JPEG comment (COM segment) or a PNG `tEXt` chunk,
+//! both of which image tools generally carry through copies and renames.
+//! Pixel data is copied byte for byte.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use crate::bindings::error::Naps2Error;
+use crate::bindings::images::ImageFormat;
+use crate::bindings::resolution::crc32;
+
+/// PNG text keyword, and JPEG comment prefix before `=`
+const KEY: &str = "NAPS2PageIndex";
+
+fn image_error(path: &Path, reason: &str) -> anyhow::Error {
+    Naps2Error::ImageError(format!("Cannot store page index in {:?}: {}", path, reason)).into()
+}
+
+/// Parse a `KEY=N` JPEG comment
+fn parse_comment(comment: &[u8]) -> Option<usize> {
+    let text = std::str::from_utf8(comment).ok()?;
+    text.strip_prefix(KEY)?.strip_prefix('=')?.trim().parse().ok()
+}
+
+/// Byte ranges of the JPEG segments before the start of scan, as `(marker, start, end)`
+///
+/// Stops at the first corrupt segment; everything from there on is copied unchanged.
+fn jpeg_segments(data: &[u8]) -> Vec<(u8, usize, usize)> {
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    while pos + 4 <= data.len() && data[pos] == 0xFF {
+        let marker = data[pos + 1];
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        // A length below 2 doesn't even cover itself; treat the rest as opaque
+        if length < 2 {
+            break;
+        }
+        let end = (pos + 2 + length).min(data.len());
+        segments.push((marker, pos, end));
+        pos = end;
+    }
+    segments
+}
+
+/// Byte ranges of the PNG chunks, as `(type, start, end)`
+fn png_chunks(data: &[u8]) -> Vec<([u8; 4], usize, usize)> {
+    let mut chunks = Vec::new();
+    let mut pos = 8;
+    while pos + 12 <= data.len() {
+        let length = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+        let end = pos + 12 + length;
+        if end > data.len() {
+            break;
+        }
+        chunks.push(([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]], pos, end));
+        pos = end;
+    }
+    chunks
+}
+
+/// Replace any page index comment and insert a new one after the APPn segments
+fn set_jpeg_index(data: &[u8], index: usize) -> Vec<u8> {
+    let segments = jpeg_segments(data);
+    let insert_at = segments.iter()
+        .take_while(|(marker, _, _)| (0xE0..=0xEF).contains(marker))
+        .last()
+        .map_or(2, |(_, _, end)| *end);
+    
+    let text = format!("{}={}", KEY, index);
+    let mut comment = vec![0xFF, 0xFE];
+    comment.extend_from_slice(&((text.len() + 2) as u16).to_be_bytes());
+    comment.extend_from_slice(text.as_bytes());
+    
+    let mut output = Vec::with_capacity(data.len() + comment.len());
+    output.extend_from_slice(&data[..2]);
+    if insert_at == 2 {
+        output.extend_from_slice(&comment);
+    }
+    
+    // Segments are contiguous, so copy them through, leaving out any old index
+    let mut pos = 2;
+    for (marker, start, end) in segments {
+        if !(marker == 0xFE && parse_comment(&data[start + 4..end]).is_some()) {
+            output.extend_from_slice(&data[start..end]);
+        }
+        if end == insert_at {
+            output.extend_from_slice(&comment);
+        }
+        pos = end;
+    }
+    output.extend_from_slice(&data[pos..]);
+    output
+}
+
+/// Replace any page index text chunk and insert a new one after IHDR
+fn set_png_index(path: &Path, data: &[u8], index: usize) -> Result<Vec<u8>> {
+    let mut text = b"tEXt".to_vec();
+    text.extend_from_slice(KEY.as_bytes());
+    text.push(0);
+    text.extend_from_slice(index.to_string().as_bytes());
+    
+    let mut chunk = ((text.len() - 4) as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(&text);
+    chunk.extend_from_slice(&crc32(&text).to_be_bytes());
+    
+    let chunks = png_chunks(data);
+    if chunks.first().map(|(kind, _, _)| kind) != Some(b"IHDR") {
+        return Err(image_error(path, "PNG has no IHDR chunk"));
+    }
+    
+    let mut output = data[..8].to_vec();
+    let mut last = 8;
+    for (kind, start, end) in chunks {
+        last = end;
+        if &kind == b"tEXt" && png_text_index(&data[start + 8..end - 4]).is_some() {
+            continue;
+        }
+        output.extend_from_slice(&data[start..end]);
+        if &kind == b"IHDR" {
+            output.extend_from_slice(&chunk);
+        }
+    }
+    output.extend_from_slice(&data[last..]);
+    
+    Ok(output)
+}
+
+/// Parse a `KEY\0N` PNG text chunk body
+fn png_text_index(body: &[u8]) -> Option<usize> {
+    let value = body.strip_prefix(KEY.as_bytes())?.strip_prefix(&[0])?;
+    std::str::from_utf8(value).ok()?.trim().parse().ok()
+}
+
+/// Record `index` in a JPEG or PNG file, replacing any index already there
+pub(crate) fn set_page_index(path: &Path, index: usize) -> Result<()> {
+    let data = fs::read(path).with_context(|| format!("Failed to read image {:?}", path))?;
+    
+    let updated = match ImageFormat::from_magic_bytes(&data) {
+        Some(ImageFormat::Jpeg) => set_jpeg_index(&data, index),
+        Some(ImageFormat::Png) => set_png_index(path, &data, index)?,
+        _ => return Err(image_error(path, "only JPEG and PNG are supported")),
+    };
+    
+    fs::write(path, updated).with_context(|| format!("Failed to write image {:?}", path))?;
+    Ok(())
+}
+
+/// Read the index recorded by `set_page_index`, if any
+pub(crate) fn page_index(path: &Path) -> Result<Option<usize>> {
+    let data = fs::read(path).with_context(|| format!("Failed to read image {:?}", path))?;
+    
+    Ok(match ImageFormat::from_magic_bytes(&data) {
+        Some(ImageFormat::Jpeg) => jpeg_segments(&data).into_iter()
+            .filter(|(marker, _, _)| *marker == 0xFE)
+            .find_map(|(_, start, end)| parse_comment(&data[start + 4..end])),
+        Some(ImageFormat::Png) => png_chunks(&data).into_iter()
+            .filter(|(kind, _, _)| kind == b"tEXt")
+            .find_map(|(_, start, end)| png_text_index(&data[start + 8..end - 4])),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const JPEG: &[u8] = &[
+        0xFF, 0xD8,
+        0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0, 1, 2, 1, 0, 0x48, 0, 0x48, 0, 0,
+        0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34,
+        0xFF, 0xD9,
+    ];
+
+    fn png() -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+        for (kind, payload) in [(&b"IHDR"[..], &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0][..]), (b"IEND", &[])] {
+            let mut body = kind.to_vec();
+            body.extend_from_slice(payload);
+            data.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            data.extend_from_slice(&body);
+            data.extend_from_slice(&crc32(&body).to_be_bytes());
+        }
+        data
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("naps2-page-index-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Index three pages, shuffle their names, and recover the original order
+    fn round_trip(name: &str, original: &[u8], extension: &str) {
+        let dir = temp_dir(name);
+        let pages: Vec<PathBuf> = (0..3).map(|i| dir.join(format!("page-{}.{}", i, extension))).collect();
+        for (index, page) in pages.iter().enumerate() {
+            fs::write(page, original).unwrap();
+            set_page_index(page, index).unwrap();
+            // Setting it again replaces the old index rather than adding a second one
+            set_page_index(page, index).unwrap();
+        }
+        let shuffled: Vec<PathBuf> = ["c", "a", "b"].iter().map(|n| dir.join(format!("{}.{}", n, extension))).collect();
+        for (page, new_name) in pages.iter().zip(&shuffled) {
+            fs::rename(page, new_name).unwrap();
+        }
+
+        let mut found: Vec<(usize, PathBuf)> = fs::read_dir(&dir).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .map(|path| (page_index(&path).unwrap().unwrap(), path))
+            .collect();
+        found.sort();
+        assert_eq!(found.into_iter().map(|(_, path)| path).collect::<Vec<_>>(), shuffled);
+
+        // Image data after the metadata is copied through untouched
+        assert!(fs::read(&shuffled[0]).unwrap().ends_with(&original[original.len() - 8..]));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn jpeg_index_survives_renames() {
+        round_trip("jpeg", JPEG, "jpg");
+    }
+
+    #[test]
+    fn png_index_survives_renames() {
+        round_trip("png", &png(), "png");
+    }
+
+    #[test]
+    fn jpeg_index_without_app_segments() {
+        let mut data = vec![0xFF, 0xD8];
+        data.extend_from_slice(&JPEG[20..]);
+        let updated = set_jpeg_index(&data, 7);
+        assert_eq!(&updated[2..4], &[0xFF, 0xFE]);
+        assert!(updated.ends_with(&JPEG[20..]));
+    }
+
+    #[test]
+    fn short_jpeg_segment_does_not_panic() {
+        for length in [0u8, 1] {
+            let mut data = JPEG.to_vec();
+            data[5] = length;
+            let dir = temp_dir(&format!("short-{}", length));
+            let path = dir.join("page.jpg");
+            fs::write(&path, &data).unwrap();
+            assert_eq!(page_index(&path).unwrap(), None);
+            let updated = set_jpeg_index(&data, 3);
+            assert!(updated.ends_with(&data[2..]));
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+}
@@ -1,64 +1,880 @@
 //! Rust bindings for NAPS2.Pdf namespace
 
 use anyhow::{Context, Result};
+use std::cmp::Ordering;
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::bindings::document::{Document, PageRotation};
 use crate::bindings::error::Naps2Error;
+use crate::bindings::helper;
+use crate::bindings::naming::{OverwritePolicy, SavedFile};
+use crate::bindings::scan::ScannerDevice;
+use crate::bindings::images::{remove_decoded, validate_images, ColorContent, ColorThresholds, IccProfile, ImageClient};
+
+/// Ordering used when collecting image files from a directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileSort {
+    /// Plain string ordering (`page10` sorts before `page2`)
+    Lexical,
+    /// Digit runs compared numerically (`page2` sorts before `page10`)
+    Natural,
+}
+
+/// Compare two strings treating runs of ASCII digits as numbers
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let mut x_digits = String::new();
+                while let Some(c) = a_chars.peek().copied().filter(char::is_ascii_digit) {
+                    x_digits.push(c);
+                    a_chars.next();
+                }
+                let mut y_digits = String::new();
+                while let Some(c) = b_chars.peek().copied().filter(char::is_ascii_digit) {
+                    y_digits.push(c);
+                    b_chars.next();
+                }
+                
+                // Compare by magnitude first, ignoring leading zeros
+                let x_trimmed = x_digits.trim_start_matches('0');
+                let y_trimmed = y_digits.trim_start_matches('0');
+                let ordering = x_trimmed.len().cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed))
+                    .then_with(|| x_digits.len().cmp(&y_digits.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+/// Document information stored in a PDF
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PdfMetadata {
+    #[serde(rename = "Title", default)]
+    pub title: Option<String>,
+    #[serde(rename = "Author", default)]
+    pub author: Option<String>,
+    #[serde(rename = "Subject", default)]
+    pub subject: Option<String>,
+    #[serde(rename = "Keywords", default)]
+    pub keywords: Option<String>,
+    #[serde(rename = "Creator", default)]
+    pub creator: Option<String>,
+    #[serde(rename = "Producer", default)]
+    pub producer: Option<String>,
+    /// Creation date as an ISO 8601 timestamp
+    #[serde(rename = "CreationDate", default)]
+    pub creation_date: Option<String>,
+    /// Last modification date as an ISO 8601 timestamp
+    #[serde(rename = "ModDate", default)]
+    pub modification_date: Option<String>,
+}
+
+/// Format a time as an ISO 8601 UTC timestamp
+pub(crate) fn iso8601(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+impl PdfMetadata {
+    /// Fill unset fields with when and on which device the document was scanned
+    ///
+    /// Sets `creation_date` to now and `producer` to the device name and driver.
+    /// Fields that were already set explicitly are kept.
+    pub fn with_scan_provenance(mut self, device: &ScannerDevice) -> Self {
+        self.creation_date.get_or_insert_with(|| iso8601(SystemTime::now()));
+        self.producer.get_or_insert_with(|| format!("{} ({})", device.name, device.driver));
+        self
+    }
+    
+    /// Helper flags for each field that is set
+    fn to_args(&self) -> Vec<String> {
+        let fields = [
+            ("--title", &self.title),
+            ("--author", &self.author),
+            ("--subject", &self.subject),
+            ("--keywords", &self.keywords),
+            ("--creator", &self.creator),
+            ("--producer", &self.producer),
+            ("--creation-date", &self.creation_date),
+            ("--mod-date", &self.modification_date),
+        ];
+        
+        fields.iter()
+            .filter_map(|(flag, value)| value.as_ref().map(|v| [flag.to_string(), v.clone()]))
+            .flatten()
+            .collect()
+    }
+}
+
+/// Color space images are converted to when embedded in a PDF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PdfColorSpace {
+    /// Keep each image's own color space
+    #[default]
+    Preserve,
+    /// Convert every image to sRGB
+    Srgb,
+    /// Convert every image to 8-bit grayscale
+    Grayscale,
+}
+
+impl PdfColorSpace {
+    /// Convert to string for passing to the C# helper
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            PdfColorSpace::Preserve => "Preserve",
+            PdfColorSpace::Srgb => "Srgb",
+            PdfColorSpace::Grayscale => "Grayscale",
+        }
+    }
+}
+
+/// How images are compressed when embedded in a PDF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PdfImageCompression {
+    /// Keep each image's source encoding where the PDF can hold it
+    #[default]
+    Preserve,
+    /// JPEG at this quality (1-100); smallest for photos, lossy
+    Jpeg { quality: u8 },
+    /// Lossless Flate (zlib)
+    Flate,
+    /// CCITT Group 4; bilevel images only
+    Ccitt,
+    /// JBIG2, lossless generic region coding; bilevel images only
+    ///
+    /// Requires a helper built with `Feature::Jbig2`.
+    Jbig2,
+    /// JBIG2 for bilevel pages when the helper supports it, otherwise CCITT;
+    /// other pages keep their source encoding
+    Auto,
+}
+
+impl PdfImageCompression {
+    /// Convert to string for passing to the C# helper
+    pub fn to_arg(&self) -> String {
+        match self {
+            PdfImageCompression::Preserve => "Preserve".to_string(),
+            PdfImageCompression::Jpeg { quality } => format!("Jpeg:{}", quality),
+            PdfImageCompression::Flate => "Flate".to_string(),
+            PdfImageCompression::Ccitt => "Ccitt".to_string(),
+            PdfImageCompression::Jbig2 => "Jbig2".to_string(),
+            PdfImageCompression::Auto => "Auto".to_string(),
+        }
+    }
+}
+
+/// An image embedded in a PDF, as reported by `PdfClient::list_images`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PdfImageInfo {
+    /// Zero-based index of the page the image is drawn on
+    #[serde(rename = "Page")]
+    pub page: usize,
+    /// Width in pixels
+    #[serde(rename = "Width")]
+    pub width: u32,
+    /// Height in pixels
+    #[serde(rename = "Height")]
+    pub height: u32,
+    #[serde(rename = "BitsPerComponent")]
+    pub bits_per_component: u8,
+    /// PDF filter the image data is stored with, such as `DCTDecode` or `CCITTFaxDecode`
+    #[serde(rename = "Filter", default)]
+    pub filter: Option<String>,
+    /// Size of the stored image data in bytes
+    #[serde(rename = "Length")]
+    pub length: u64,
+}
+
+/// Where PDF export takes its outline (bookmarks) from
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PdfBookmarks {
+    /// No outline
+    #[default]
+    None,
+    /// A bookmark titled with the label of each labelled page, by page index
+    Labels(Vec<Option<String>>),
+    /// A bookmark for each heading found by OCR; requires `ocr_language`
+    OcrHeadings,
+}
+
+/// Options for PDF export
+#[derive(Debug, Clone, Default)]
+pub struct PdfExportOptions {
+    /// Produce a PDF/A-2b compliant file for long-term archiving
+    pub pdf_a: bool,
+    /// Run OCR in this language and add a searchable text layer
+    pub ocr_language: Option<String>,
+    /// Document information to embed
+    pub metadata: PdfMetadata,
+    /// Color profile to embed as the document's output intent
+    pub icc_profile: Option<IccProfile>,
+    /// Keep the profile embedded in each source image, if any
+    ///
+    /// Images without a profile fall back to `icc_profile` when it is set.
+    pub preserve_source_profile: bool,
+    /// Record the scan date and this device in any metadata fields left unset
+    pub scan_provenance: Option<ScannerDevice>,
+    /// Color space for embedded images; `Grayscale` gives much smaller text documents
+    pub color_space: PdfColorSpace,
+    /// Per-page overrides of `color_space`, by page index
+    ///
+    /// Lets a mixed batch keep a color cover while text pages go grayscale.
+    /// Pages past the end of the list use `color_space`.
+    pub page_color_spaces: Vec<PdfColorSpace>,
+    /// Pick each page's color space from its content, using these thresholds
+    ///
+    /// Pages found to be grayscale or bilevel are exported as `Grayscale`;
+    /// color pages use `color_space`. Ignored when `page_color_spaces` is set.
+    /// Costs one `ImageClient::detect_color_content_with` call per page.
+    pub detect_color_spaces: Option<ColorThresholds>,
+    /// Outline to add for navigating long documents
+    pub bookmarks: PdfBookmarks,
+    /// Downsample embedded images to this resolution, or `None` to keep them as scanned
+    ///
+    /// OCR still runs on the full-resolution images, so the text layer is unaffected.
+    pub image_dpi: Option<u32>,
+    /// Encoding for embedded images, trading file size against quality
+    pub image_compression: PdfImageCompression,
+}
+
+impl PdfExportOptions {
+    /// Searchable PDF with images reduced to 150 DPI at JPEG quality 60
+    ///
+    /// Typically a fraction of the size of a full-resolution export, with the
+    /// text layer recognized from the original scans.
+    pub fn compact_searchable(ocr_language: &str) -> Self {
+        Self {
+            ocr_language: Some(ocr_language.to_string()),
+            image_dpi: Some(150),
+            image_compression: PdfImageCompression::Jpeg { quality: 60 },
+            ..Self::default()
+        }
+    }
+}
+
+/// A file embedded in a PDF
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PdfAttachment {
+    /// Name used to extract the attachment
+    #[serde(rename = "Name")]
+    pub name: String,
+    /// Size in bytes
+    #[serde(rename = "Size")]
+    pub size: u64,
+    #[serde(rename = "MimeType", default)]
+    pub mime_type: Option<String>,
+    #[serde(rename = "Description", default)]
+    pub description: Option<String>,
+}
+
+/// A PDF page rendered to an image by `PdfClient::import_pdf_pages`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ImportedPage {
+    /// Path of the rendered image
+    #[serde(rename = "Path")]
+    pub path: String,
+    /// Width of the original page in points (1/72 inch)
+    #[serde(rename = "Width")]
+    pub width: f64,
+    /// Height of the original page in points (1/72 inch)
+    #[serde(rename = "Height")]
+    pub height: f64,
+    /// Resolution the page was rendered at
+    #[serde(rename = "Dpi")]
+    pub dpi: u32,
+}
+
+/// A structural problem found by `PdfClient::validate`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PdfIssue {
+    /// What is wrong, e.g. a broken cross-reference table
+    #[serde(rename = "Message")]
+    pub message: String,
+    /// Whether `PdfClient::repair` can fix it
+    #[serde(rename = "Repairable", default)]
+    pub repairable: bool,
+}
+
+/// Outcome of checking a PDF's structure
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PdfValidation {
+    /// Number of pages the PDF library could read
+    #[serde(rename = "PageCount", default)]
+    pub page_count: usize,
+    #[serde(rename = "Issues", default)]
+    pub issues: Vec<PdfIssue>,
+}
+
+impl PdfValidation {
+    /// Whether no issues were found
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// A PDF assembled from several batches of pages, created by `PdfClient::builder`
+///
+/// Each `add_pages` call appends its pages to the file on disk as an
+/// incremental update, so earlier pages are never re-read or rewritten and the
+/// file is a valid PDF between calls. `finalize` rewrites the file once in full,
+/// applying the metadata and PDF/A options and compacting the updates.
+#[derive(Debug)]
+pub struct PdfBuilder<'a> {
+    client: &'a PdfClient,
+    path: PathBuf,
+    options: PdfExportOptions,
+    page_count: usize,
+}
+
+impl PdfBuilder<'_> {
+    /// Append images as new pages, writing them to disk before returning
+    ///
+    /// OCR, color space and image options apply to each batch as it is added.
+    /// Returns the total number of pages so far.
+    pub fn add_pages(&mut self, image_paths: &[String]) -> Result<usize> {
+        // Document-wide options are applied once by finalize
+        let options = PdfExportOptions {
+            pdf_a: false,
+            metadata: PdfMetadata::default(),
+            scan_provenance: None,
+            ..self.options.clone()
+        };
+        let subcommand = if self.path.exists() { "append" } else { "export" };
+        self.client.export_command(subcommand, &self.path, image_paths, &options, &[])?;
+        
+        self.page_count += image_paths.len();
+        Ok(self.page_count)
+    }
+    
+    /// Number of pages in the PDF so far
+    pub fn page_count(&self) -> usize {
+        self.page_count
+    }
+    
+    /// Path of the PDF being assembled
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    /// Apply document-wide options and rewrite the PDF in full, returning the page count
+    pub fn finalize(self) -> Result<usize> {
+        if self.page_count == 0 {
+            return Err(Naps2Error::PdfError("No pages were added to the PDF".to_string()).into());
+        }
+        
+        let mut cmd = self.client.command();
+        cmd.args(["pdf", "finalize", self.path.to_string_lossy().as_ref()]);
+        
+        // Add document-wide options
+        if self.options.pdf_a {
+            cmd.arg("--pdfa");
+        }
+        match &self.options.scan_provenance {
+            Some(device) => cmd.args(self.options.metadata.clone().with_scan_provenance(device).to_args()),
+            None => cmd.args(self.options.metadata.to_args()),
+        };
+        
+        // Execute the helper application
+        helper::run(&mut cmd)?;
+        
+        Ok(self.page_count)
+    }
+}
 
 /// Client for PDF operations
+#[derive(Debug)]
 pub struct PdfClient {
     helper_path: PathBuf,
+    working_dir: Option<PathBuf>,
 }
 
 impl PdfClient {
     /// Create a new PDF client with the path to the helper application
     pub fn new(helper_path: PathBuf) -> Self {
-        Self { helper_path }
+        Self { helper_path, working_dir: None }
+    }
+    
+    /// Resolve relative paths against `working_dir` and run the helper from there
+    pub fn with_working_dir<P: Into<PathBuf>>(mut self, working_dir: P) -> Self {
+        self.working_dir = Some(working_dir.into());
+        self
+    }
+    
+    /// Start building a helper command
+    fn command(&self) -> Command {
+        helper::command(&self.helper_path, self.working_dir.as_deref())
+    }
+    
+    /// Resolve a relative path against `working_dir`, as the helper would
+    fn resolve(&self, path: &Path) -> PathBuf {
+        helper::resolve(self.working_dir.as_deref(), path)
     }
     
     /// Export a collection of images to a PDF file
+    ///
+    /// With the `native` feature enabled, export falls back to
+    /// `export_pdf_native` when the helper executable can't be found.
     pub fn export_pdf<P: AsRef<Path>>(&self, output_path: P, image_paths: &[String]) -> Result<()> {
-        let mut cmd = Command::new(&self.helper_path);
-        cmd.args(["pdf", "export", output_path.as_ref().to_string_lossy().as_ref()]);
+        let result = self.export_pdf_with_options(output_path.as_ref(), image_paths, &PdfExportOptions::default());
+        
+        #[cfg(feature = "native")]
+        if result.as_ref().is_err_and(helper::is_missing_helper) {
+            return self.export_pdf_native(output_path, image_paths);
+        }
+        
+        result
+    }
+    
+    /// Start assembling a PDF that pages are added to over several calls
+    ///
+    /// If `output_path` already exists, pages are added after its existing
+    /// pages, so assembly can continue in a later session. See `PdfBuilder`.
+    pub fn builder<P: AsRef<Path>>(&self, output_path: P, options: &PdfExportOptions) -> Result<PdfBuilder<'_>> {
+        if !options.page_color_spaces.is_empty() || options.detect_color_spaces.is_some()
+            || matches!(options.bookmarks, PdfBookmarks::Labels(_)) {
+            return Err(Naps2Error::InvalidOptionsError(
+                "Per-page color spaces and bookmark labels can't be used when building a PDF incrementally".to_string()).into());
+        }
+        
+        let path = self.resolve(output_path.as_ref());
+        let page_count = if path.exists() { self.page_rotations(&path)?.len() } else { 0 };
+        
+        Ok(PdfBuilder {
+            client: self,
+            path,
+            options: options.clone(),
+            page_count,
+        })
+    }
+    
+    /// Export a collection of images to a PDF file with OCR, PDF/A or metadata options
+    pub fn export_pdf_with_options<P: AsRef<Path>>(&self, output_path: P, image_paths: &[String],
+                                                   options: &PdfExportOptions) -> Result<()> {
+        self.export_command("export", output_path.as_ref(), image_paths, options, &[])
+    }
+    
+    /// Export to `output_path`, applying `policy` if it already exists
+    ///
+    /// Nothing is exported when the file is skipped. The other export methods
+    /// replace an existing file.
+    pub fn export_pdf_with_policy<P: AsRef<Path>>(&self, output_path: P, image_paths: &[String],
+                                                  options: &PdfExportOptions, policy: OverwritePolicy) -> Result<SavedFile> {
+        let intended = self.resolve(output_path.as_ref());
+        let (target, action) = policy.resolve(intended.clone());
+        if let Some(target) = &target {
+            self.export_pdf_with_options(target, image_paths, options)?;
+        }
+        Ok(SavedFile { path: target.unwrap_or(intended).to_string_lossy().to_string(), action })
+    }
+    
+    /// Export a document, applying each page's recorded rotation
+    ///
+    /// Rotations are written as PDF page rotation, so the images are embedded
+    /// without being re-encoded.
+    pub fn export_document<P: AsRef<Path>>(&self, output_path: P, document: &Document,
+                                           options: &PdfExportOptions) -> Result<()> {
+        let rotations: Vec<(usize, u16)> = document.pages().iter().enumerate()
+            .filter(|(_, page)| page.rotation != PageRotation::None)
+            .map(|(i, page)| (i, page.rotation.degrees()))
+            .collect();
+        
+        self.export_command("export", output_path.as_ref(), &document.image_paths(), options, &rotations)
+    }
+    
+    /// Run `pdf export` or `pdf append` with the given options and `(page index, degrees)` rotations
+    fn export_command(&self, subcommand: &str, output_path: &Path, image_paths: &[String],
+                      options: &PdfExportOptions, rotations: &[(usize, u16)]) -> Result<()> {
+        validate_images(image_paths, self.working_dir.as_deref())?;
+        if options.page_color_spaces.len() > image_paths.len() {
+            return Err(Naps2Error::InvalidOptionsError(format!(
+                "{} page color spaces given for {} pages", options.page_color_spaces.len(), image_paths.len())).into());
+        }
+        
+        match &options.bookmarks {
+            PdfBookmarks::Labels(labels) if labels.len() > image_paths.len() => {
+                return Err(Naps2Error::InvalidOptionsError(format!(
+                    "{} bookmark labels given for {} pages", labels.len(), image_paths.len())).into());
+            }
+            PdfBookmarks::OcrHeadings if options.ocr_language.is_none() => {
+                return Err(Naps2Error::InvalidOptionsError(
+                    "Bookmarks from OCR headings require an OCR language".to_string()).into());
+            }
+            _ => {}
+        }
+        if options.image_dpi == Some(0) {
+            return Err(Naps2Error::InvalidOptionsError("image_dpi must be greater than zero".to_string()).into());
+        }
+        if let PdfImageCompression::Jpeg { quality: quality @ (0 | 101..) } = options.image_compression {
+            return Err(Naps2Error::InvalidOptionsError(
+                format!("JPEG compression quality must be between 1 and 100, got {}", quality)).into());
+        }
+        
+        let mut images = ImageClient::new(self.helper_path.clone());
+        if let Some(dir) = &self.working_dir {
+            images = images.with_working_dir(dir.clone());
+        }
+        let inputs = images.decode_heif_inputs(image_paths)?;
+        
+        let page_color_spaces = match &options.detect_color_spaces {
+            Some(thresholds) if options.page_color_spaces.is_empty() => {
+                let detected = inputs.iter()
+                    .map(|path| Ok(match images.detect_color_content_with(path, thresholds)? {
+                        ColorContent::Color => options.color_space,
+                        content => content.pdf_color_space(),
+                    }))
+                    .collect::<Result<Vec<_>>>();
+                if detected.is_err() {
+                    remove_decoded(image_paths, &inputs);
+                }
+                detected?
+            }
+            _ => options.page_color_spaces.clone(),
+        };
+        
+        let mut cmd = self.command();
+        cmd.args(["pdf", subcommand, output_path.to_string_lossy().as_ref()]);
+        
+        // Add export options
+        if options.pdf_a {
+            cmd.arg("--pdfa");
+        }
+        if let Some(language) = &options.ocr_language {
+            cmd.args(["--ocr", language]);
+        }
+        match &options.scan_provenance {
+            Some(device) => cmd.args(options.metadata.clone().with_scan_provenance(device).to_args()),
+            None => cmd.args(options.metadata.to_args()),
+        };
+        if let Some(profile) = &options.icc_profile {
+            cmd.args(["--icc-profile", profile.path().to_string_lossy().as_ref()]);
+        }
+        if options.preserve_source_profile {
+            cmd.arg("--preserve-icc");
+        }
+        if options.color_space != PdfColorSpace::Preserve {
+            cmd.args(["--color-space", options.color_space.to_string()]);
+        }
+        for (index, color_space) in page_color_spaces.iter().enumerate() {
+            if *color_space != options.color_space {
+                cmd.args(["--page-color-space", &format!("{}:{}", index, color_space.to_string())]);
+            }
+        }
+        if let Some(dpi) = options.image_dpi {
+            cmd.args(["--image-dpi", &dpi.to_string()]);
+        }
+        if options.image_compression != PdfImageCompression::Preserve {
+            cmd.args(["--compression", &options.image_compression.to_arg()]);
+        }
+        match &options.bookmarks {
+            PdfBookmarks::None => {}
+            PdfBookmarks::Labels(labels) => {
+                for (index, label) in labels.iter().enumerate() {
+                    if let Some(label) = label {
+                        cmd.args(["--bookmark", &format!("{}:{}", index, label)]);
+                    }
+                }
+            }
+            PdfBookmarks::OcrHeadings => {
+                cmd.arg("--bookmarks-from-ocr");
+            }
+        }
+        for (index, degrees) in rotations {
+            cmd.args(["--rotate", &format!("{}:{}", index, degrees)]);
+        }
         
         // Add image paths
-        for path in image_paths {
+        for path in &inputs {
             cmd.arg(path);
         }
         
         // Execute the helper application
-        let output = cmd.output()
-            .with_context(|| format!("Failed to execute helper at {:?}", self.helper_path))?;
+        let result = helper::run(&mut cmd);
+        remove_decoded(image_paths, &inputs);
+        result?;
+        
+        Ok(())
+    }
+    
+    /// Export a collection of images to a PDF file without the helper
+    ///
+    /// Only JPEG and 8-bit grayscale/RGB PNG images are supported, and pages are
+    /// sized from each image's embedded resolution (300 DPI if missing). Unlike
+    /// the helper path there is no OCR text layer, encryption, metadata or PDF/A.
+    #[cfg(feature = "native")]
+    pub fn export_pdf_native<P: AsRef<Path>>(&self, output_path: P, image_paths: &[String]) -> Result<()> {
+        let image_paths: Vec<String> = image_paths.iter()
+            .map(|path| self.resolve(Path::new(path)).to_string_lossy().to_string())
+            .collect();
+        crate::bindings::native_pdf::export_pdf(self.resolve(output_path.as_ref()), &image_paths)
+    }
+    
+    /// Export all images in a directory to a PDF file
+    ///
+    /// Only files whose extension matches one of `extensions` (case-insensitive,
+    /// without the leading dot) are included, ordered by file name using `sort`.
+    pub fn export_pdf_from_dir<P: AsRef<Path>, D: AsRef<Path>>(&self, output_path: P, dir: D,
+                                                               extensions: &[&str], sort: FileSort) -> Result<Vec<String>> {
+        let dir = &self.resolve(dir.as_ref());
+        let entries = fs::read_dir(dir)
+            .with_context(|| format!("Failed to read directory {:?}", dir))?;
+        
+        let mut image_paths = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
             
-        // Check if the command was successful
-        if !output.status.success() {
-            let error_message = String::from_utf8_lossy(&output.stderr).to_string();
-            return Err(Naps2Error::HelperExecutionError(error_message).into());
+            let matches = path.extension()
+                .map(|ext| extensions.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+                .unwrap_or(false);
+            if matches {
+                image_paths.push(path.to_string_lossy().to_string());
+            }
         }
         
-        Ok(())
+        if image_paths.is_empty() {
+            return Err(Naps2Error::PdfError(
+                format!("No images matching {:?} found in {:?}", extensions, dir)).into());
+        }
+        
+        match sort {
+            FileSort::Lexical => image_paths.sort(),
+            FileSort::Natural => image_paths.sort_by(|a, b| natural_cmp(a, b)),
+        }
+        
+        self.export_pdf(output_path, &image_paths)?;
+        Ok(image_paths)
     }
     
     /// Import a PDF file into a collection of images
+    ///
+    /// Use `import_pdf_pages` to also get the original page sizes.
     pub fn import_pdf<P: AsRef<Path>>(&self, pdf_path: P) -> Result<Vec<String>> {
-        let mut cmd = Command::new(&self.helper_path);
+        let mut cmd = self.command();
         cmd.args(["pdf", "import", pdf_path.as_ref().to_string_lossy().as_ref()]);
         
         // Execute the helper application
-        let output = cmd.output()
-            .with_context(|| format!("Failed to execute helper at {:?}", self.helper_path))?;
-            
-        // Check if the command was successful
-        if !output.status.success() {
-            let error_message = String::from_utf8_lossy(&output.stderr).to_string();
-            return Err(Naps2Error::HelperExecutionError(error_message).into());
-        }
+        let output = helper::run(&mut cmd)?;
         
         // Parse the JSON output
         let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let image_paths: Vec<String> = serde_json::from_str(&stdout)
-            .map_err(|e| Naps2Error::HelperOutputError(format!("JSON parse error: {}", e)))?;
+        let image_paths: Vec<String> = helper::parse_json(&stdout)?;
             
         Ok(image_paths)
     }
+    
+    /// Import a PDF file into images, keeping each page's original geometry
+    ///
+    /// Needs a helper that supports `pdf import --details`.
+    pub fn import_pdf_pages<P: AsRef<Path>>(&self, pdf_path: P) -> Result<Vec<ImportedPage>> {
+        let mut cmd = self.command();
+        cmd.args(["pdf", "import", pdf_path.as_ref().to_string_lossy().as_ref(), "--details"]);
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let pages: Vec<ImportedPage> = helper::parse_json(&stdout)?;
+            
+        Ok(pages)
+    }
+    
+    /// Read the document information of an existing PDF
+    ///
+    /// Fields the PDF doesn't set are `None`, so the result can be passed back
+    /// as `PdfExportOptions::metadata` to carry it over to a re-exported file.
+    pub fn read_metadata<P: AsRef<Path>>(&self, pdf_path: P) -> Result<PdfMetadata> {
+        let mut cmd = self.command();
+        cmd.args(["pdf", "metadata", pdf_path.as_ref().to_string_lossy().as_ref()]);
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let metadata: PdfMetadata = helper::parse_json(&stdout)?;
+            
+        Ok(metadata)
+    }
+    
+    /// List the images embedded in a PDF with the encoding each is stored in
+    ///
+    /// Useful for checking what `PdfExportOptions::image_compression` produced,
+    /// or where the bytes of an unexpectedly large PDF are going.
+    pub fn list_images<P: AsRef<Path>>(&self, pdf_path: P) -> Result<Vec<PdfImageInfo>> {
+        let mut cmd = self.command();
+        cmd.args(["pdf", "images", pdf_path.as_ref().to_string_lossy().as_ref()]);
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let images: Vec<PdfImageInfo> = helper::parse_json(&stdout)?;
+            
+        Ok(images)
+    }
+    
+    /// List the files embedded in a PDF
+    ///
+    /// Returns an empty list for PDFs without attachments.
+    pub fn list_attachments<P: AsRef<Path>>(&self, pdf_path: P) -> Result<Vec<PdfAttachment>> {
+        let mut cmd = self.command();
+        cmd.args(["pdf", "attachments", pdf_path.as_ref().to_string_lossy().as_ref()]);
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let attachments: Vec<PdfAttachment> = helper::parse_json(&stdout)?;
+            
+        Ok(attachments)
+    }
+    
+    /// Write the attachment called `name` to `output_path`, returning the path written
+    pub fn extract_attachment<P: AsRef<Path>, Q: AsRef<Path>>(&self, pdf_path: P, name: &str,
+                                                              output_path: Q) -> Result<String> {
+        let mut cmd = self.command();
+        cmd.args([
+            "pdf",
+            "extract-attachment",
+            pdf_path.as_ref().to_string_lossy().as_ref(),
+            name,
+            output_path.as_ref().to_string_lossy().as_ref(),
+        ]);
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let path: String = helper::parse_json(&stdout)?;
+            
+        Ok(path)
+    }
+    
+    /// Check a PDF for structural problems that strict viewers may reject
+    ///
+    /// A file the helper can't parse at all is reported as an error rather than
+    /// as an issue.
+    pub fn validate<P: AsRef<Path>>(&self, pdf_path: P) -> Result<PdfValidation> {
+        let mut cmd = self.command();
+        cmd.args(["pdf", "validate", pdf_path.as_ref().to_string_lossy().as_ref()]);
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let validation: PdfValidation = helper::parse_json(&stdout)?;
+            
+        Ok(validation)
+    }
+    
+    /// Rewrite a PDF with a clean structure, returning the validation of the result
+    ///
+    /// The document is loaded leniently and saved afresh to `output_path`, which
+    /// rebuilds the cross-reference table and drops unreachable objects. The input
+    /// file is left untouched.
+    pub fn repair<P: AsRef<Path>, Q: AsRef<Path>>(&self, pdf_path: P, output_path: Q) -> Result<PdfValidation> {
+        let mut cmd = self.command();
+        cmd.args([
+            "pdf",
+            "repair",
+            pdf_path.as_ref().to_string_lossy().as_ref(),
+            output_path.as_ref().to_string_lossy().as_ref(),
+        ]);
+        helper::run(&mut cmd)?;
+        
+        self.validate(output_path)
+    }
+    
+    /// Read the `/Rotate` value of every page, in degrees clockwise
+    pub fn page_rotations<P: AsRef<Path>>(&self, pdf_path: P) -> Result<Vec<u16>> {
+        let mut cmd = self.command();
+        cmd.args(["pdf", "rotations", pdf_path.as_ref().to_string_lossy().as_ref()]);
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let rotations: Vec<u16> = helper::parse_json(&stdout)?;
+            
+        Ok(rotations)
+    }
+    
+    /// Set the `/Rotate` value of individual pages, writing the result to `output_path`
+    ///
+    /// `rotations` pairs a zero-based page index with a clockwise angle in
+    /// degrees, which must be a multiple of 90 (negative angles count
+    /// counterclockwise). The angle replaces any existing rotation. Page content
+    /// is copied untouched, so this is lossless and much faster than re-rendering.
+    pub fn rotate_pages<P: AsRef<Path>, Q: AsRef<Path>>(&self, pdf_path: P, rotations: &[(usize, i32)],
+                                                        output_path: Q) -> Result<()> {
+        let page_count = self.page_rotations(&pdf_path)?.len();
+        
+        let mut cmd = self.command();
+        cmd.args([
+            "pdf",
+            "rotate",
+            pdf_path.as_ref().to_string_lossy().as_ref(),
+            output_path.as_ref().to_string_lossy().as_ref(),
+        ]);
+        
+        for &(index, degrees) in rotations {
+            if index >= page_count {
+                return Err(Naps2Error::InvalidOptionsError(format!(
+                    "Page index {} is out of range for a {}-page PDF", index, page_count)).into());
+            }
+            if degrees % 90 != 0 {
+                return Err(Naps2Error::InvalidOptionsError(format!(
+                    "Page rotation must be a multiple of 90 degrees, got {}", degrees)).into());
+            }
+            cmd.arg(format!("{}:{}", index, degrees.rem_euclid(360)));
+        }
+        
+        // Execute the helper application
+        helper::run(&mut cmd)?;
+        
+        Ok(())
+    }
 }
@@ -0,0 +1,375 @@
+//! Fluent scan, clean-up and export chains
+
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use crate::bindings::error::Naps2Error;
+use crate::bindings::images::{BinarizeMethod, CleanOptions, ImageClient, RgbColor};
+use crate::bindings::pdf::PdfExportOptions;
+use crate::bindings::scan::{ColorMode, PaperSource, ScanOptions, ScanResult};
+use crate::bindings::Naps2Client;
+
+/// A processed page's path and resolution, or why processing failed
+type PageOutcome = Result<(String, Option<u32>)>;
+
+/// Most pages `ProcessingPipeline::apply` processes at once
+pub const MAX_PROCESSING_WORKERS: usize = 8;
+
+/// One post-processing step, applied to a page through the matching `ImageClient` method
+#[derive(Debug, Clone)]
+pub enum ProcessingStep {
+    /// Straighten the page; see `ImageClient::deskew`
+    Deskew,
+    /// Turn a page fed in upside down the right way up; see `ImageClient::fix_upside_down`
+    FixUpsideDown,
+    /// Crop to the detected page edges; see `ImageClient::crop_to_document`
+    CropToDocument,
+    /// Crop to the region that differs from the background; see `ImageClient::content_bounds`
+    CropToContent,
+    /// Rotate clockwise by this many degrees, growing the canvas and filling with white
+    Rotate(f32),
+    /// Remove isolated specks; see `ImageClient::despeckle`
+    Despeckle,
+    /// Run the enabled cleanup steps; see `ImageClient::clean_document`
+    Clean(CleanOptions),
+    /// Convert to pure black and white; see `ImageClient::binarize`
+    Binarize(BinarizeMethod),
+    /// Change brightness and contrast; see `ImageClient::adjust`
+    Adjust { brightness: i32, contrast: i32 },
+    /// Downscale from the page's scan resolution to this DPI; see `ImageClient::resample`
+    Resample(u32),
+}
+
+impl ProcessingStep {
+    /// Apply the step to one page scanned at `dpi`, returning the new path and resolution
+    fn apply(&self, images: &ImageClient, path: &str, dpi: Option<u32>) -> PageOutcome {
+        let path = match self {
+            ProcessingStep::Deskew => images.deskew(path)?,
+            ProcessingStep::FixUpsideDown => images.fix_upside_down(path)?.path,
+            ProcessingStep::CropToDocument => images.crop_to_document(path)?.path,
+            ProcessingStep::CropToContent => images.crop(path, &images.content_bounds(path)?)?,
+            ProcessingStep::Rotate(degrees) => images.rotate(path, *degrees, RgbColor::WHITE, true)?,
+            ProcessingStep::Despeckle => images.despeckle(path)?,
+            ProcessingStep::Clean(options) => images.clean_document(path, options)?,
+            ProcessingStep::Binarize(method) => images.binarize(path, *method)?,
+            ProcessingStep::Adjust { brightness, contrast } => images.adjust(path, *brightness, *contrast)?,
+            ProcessingStep::Resample(target) => {
+                let source = dpi.ok_or_else(|| Naps2Error::InvalidOptionsError(format!(
+                    "{} has no recorded resolution to resample from", path)))?;
+                return Ok((images.resample(path, source, *target)?, Some(*target)));
+            }
+        };
+        Ok((path, dpi))
+    }
+}
+
+/// An ordered list of steps run on every page of a scan
+///
+/// Each page goes through the steps in order, so `[Deskew, CropToContent]`
+/// and `[CropToContent, Deskew]` give different results. Pages are processed
+/// in parallel, up to `MAX_PROCESSING_WORKERS` at a time.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessingPipeline {
+    pub steps: Vec<ProcessingStep>,
+}
+
+impl ProcessingPipeline {
+    /// Create a pipeline that runs `steps` in order
+    pub fn new(steps: Vec<ProcessingStep>) -> Self {
+        Self { steps }
+    }
+    
+    /// Run the steps on every page of `result`, returning it with the processed pages
+    ///
+    /// Page order and everything else about the scan is kept; `page_dpis` is
+    /// updated by any `Resample` step. Intermediate images are deleted as each
+    /// step replaces them, as are the scanned pages when they live in the
+    /// scan's temp directory. The first failure is returned, after the pages
+    /// already in progress have finished.
+    pub fn apply(&self, images: &ImageClient, mut result: ScanResult) -> Result<ScanResult> {
+        let pages = &result.image_paths;
+        let temp_directory = Path::new(&result.temp_directory);
+        let owns_pages = |page: &str| !result.temp_directory.is_empty() && Path::new(page).starts_with(temp_directory);
+        let workers = thread::available_parallelism().map_or(1, |n| n.get())
+            .min(MAX_PROCESSING_WORKERS)
+            .min(pages.len());
+        
+        // Workers claim pages in turn and store each outcome at its page index
+        let next = AtomicUsize::new(0);
+        let processed: Mutex<Vec<Option<PageOutcome>>> = Mutex::new(pages.iter().map(|_| None).collect());
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(page) = pages.get(index) else { break };
+                    let outcome = self.process_page(images, page, result.page_dpi(index), owns_pages(page));
+                    processed.lock().unwrap_or_else(|e| e.into_inner())[index] = Some(outcome);
+                });
+            }
+        });
+        
+        let processed = processed.into_inner().unwrap_or_else(|e| e.into_inner())
+            .into_iter()
+            .map(|outcome| outcome.unwrap_or_else(|| Err(Naps2Error::ImageError(
+                "Processing worker panicked".to_string()).into())))
+            .collect::<Result<Vec<_>>>()?;
+        
+        let has_dpis = processed.iter().all(|(_, dpi)| dpi.is_some());
+        result.image_paths = processed.iter().map(|(path, _)| path.clone()).collect();
+        if has_dpis {
+            result.page_dpis = processed.iter().filter_map(|(_, dpi)| *dpi).collect();
+        }
+        
+        Ok(result)
+    }
+    
+    /// Run the steps on one page, deleting each intermediate once the next step has replaced it
+    ///
+    /// The page itself is only deleted when `owned`, i.e. it lives in the scan's
+    /// temp directory rather than being a file the caller passed in.
+    fn process_page(&self, images: &ImageClient, page: &str, dpi: Option<u32>, owned: bool) -> PageOutcome {
+        let mut current = (page.to_string(), dpi);
+        for step in &self.steps {
+            let disposable = current.0 != page || owned;
+            match step.apply(images, &current.0, current.1) {
+                Ok(next) => {
+                    if disposable && next.0 != current.0 {
+                        let _ = fs::remove_file(&current.0);
+                    }
+                    current = next;
+                }
+                Err(e) => {
+                    if current.0 != page {
+                        let _ = fs::remove_file(&current.0);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(current)
+    }
+}
+
+/// A scan and the steps to run on its pages, created by `Naps2Client::pipeline`
+///
+/// Nothing happens until `run` or `to_pdf` is called. Steps run in the order
+/// they were added, each through the matching `ImageClient` method, e.g.
+/// `deskew` calls `ImageClient::deskew` on every page, as a `ProcessingPipeline`
+/// would. For example,
+/// `client.pipeline(id).dpi(300).feeder().deskew().ocr("eng").to_pdf("out.pdf")`
+/// scans from the feeder, straightens each page and exports a searchable PDF.
+#[derive(Clone)]
+pub struct ScanPipeline<'a> {
+    client: &'a Naps2Client,
+    device_id: String,
+    options: ScanOptions,
+    steps: Vec<ProcessingStep>,
+    export: PdfExportOptions,
+}
+
+impl<'a> ScanPipeline<'a> {
+    pub(crate) fn new(client: &'a Naps2Client, device_id: &str) -> Self {
+        Self {
+            client,
+            device_id: device_id.to_string(),
+            options: ScanOptions::default(),
+            steps: Vec::new(),
+            export: PdfExportOptions::default(),
+        }
+    }
+    
+    /// Replace all scan settings at once
+    pub fn options(mut self, options: ScanOptions) -> Self {
+        self.options = options;
+        self
+    }
+    
+    /// Scan resolution in dots per inch
+    pub fn dpi(mut self, dpi: u32) -> Self {
+        self.options.dpi = dpi;
+        self
+    }
+    
+    /// Color mode of the scanned images
+    pub fn color_mode(mut self, color_mode: ColorMode) -> Self {
+        self.options.color_mode = color_mode;
+        self
+    }
+    
+    /// Scan from the flatbed
+    pub fn flatbed(mut self) -> Self {
+        self.options.paper_source = Some(PaperSource::Flatbed);
+        self
+    }
+    
+    /// Scan from the document feeder
+    pub fn feeder(mut self) -> Self {
+        self.options.paper_source = Some(PaperSource::Feeder);
+        self
+    }
+    
+    /// Scan both sides from the document feeder
+    pub fn duplex(mut self) -> Self {
+        self.options.paper_source = Some(PaperSource::Duplex);
+        self
+    }
+    
+    /// Add any processing step
+    pub fn step(mut self, step: ProcessingStep) -> Self {
+        self.steps.push(step);
+        self
+    }
+    
+    /// Straighten each page
+    pub fn deskew(mut self) -> Self {
+        self.steps.push(ProcessingStep::Deskew);
+        self
+    }
+    
+    /// Turn pages fed in upside down the right way up
+    pub fn fix_upside_down(mut self) -> Self {
+        self.steps.push(ProcessingStep::FixUpsideDown);
+        self
+    }
+    
+    /// Run the enabled cleanup steps on each page
+    pub fn clean(mut self, options: CleanOptions) -> Self {
+        self.steps.push(ProcessingStep::Clean(options));
+        self
+    }
+    
+    /// Convert each page to pure black and white
+    pub fn binarize(mut self, method: BinarizeMethod) -> Self {
+        self.steps.push(ProcessingStep::Binarize(method));
+        self
+    }
+    
+    /// Downscale each page from the scan resolution to `dpi`
+    pub fn resample(mut self, dpi: u32) -> Self {
+        self.steps.push(ProcessingStep::Resample(dpi));
+        self
+    }
+    
+    /// Add a searchable text layer in this language when exporting to PDF
+    pub fn ocr(mut self, language: &str) -> Self {
+        self.export.ocr_language = Some(language.to_string());
+        self
+    }
+    
+    /// Replace the PDF export settings, keeping any OCR language already set
+    pub fn pdf_options(mut self, options: PdfExportOptions) -> Self {
+        let ocr_language = self.export.ocr_language.take();
+        self.export = options;
+        if self.export.ocr_language.is_none() {
+            self.export.ocr_language = ocr_language;
+        }
+        self
+    }
+    
+    /// Scan and run the steps, returning the processed pages
+    ///
+    /// The OCR language only applies to `to_pdf`.
+    pub fn run(&self) -> Result<ScanResult> {
+        let result = self.client.scan().scan(&self.device_id, &self.options)?;
+        ProcessingPipeline::new(self.steps.clone()).apply(self.client.image(), result)
+    }
+    
+    /// Scan, run the steps and export the pages to a PDF at `output_path`
+    pub fn to_pdf<P: AsRef<Path>>(&self, output_path: P) -> Result<ScanResult> {
+        let result = self.run()?;
+        self.client.pdf().export_pdf_with_options(output_path, &result.image_paths, &self.export)?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn scan(pages: &[&str], dpis: &[u32], temp_directory: &str) -> ScanResult {
+        ScanResult {
+            image_paths: pages.iter().map(|page| page.to_string()).collect(),
+            temp_directory: temp_directory.to_string(),
+            warnings: Vec::new(),
+            paper_source: None,
+            page_dpis: dpis.to_vec(),
+            timing: None,
+        }
+    }
+
+    fn no_helper() -> ImageClient {
+        ImageClient::new(PathBuf::from("/nonexistent/naps2-helper"))
+    }
+
+    #[test]
+    fn resample_without_a_dpi_fails_before_running_the_helper() {
+        let error = ProcessingStep::Resample(150).apply(&no_helper(), "page.png", None).unwrap_err();
+        assert!(matches!(error.downcast_ref::<Naps2Error>(), Some(Naps2Error::InvalidOptionsError(_))), "{}", error);
+    }
+
+    #[test]
+    fn steps_run_in_the_order_given() {
+        let resample_first = ProcessingPipeline::new(vec![ProcessingStep::Resample(150), ProcessingStep::Deskew]);
+        let error = resample_first.apply(&no_helper(), scan(&["page.png"], &[], "")).unwrap_err();
+        assert!(matches!(error.downcast_ref::<Naps2Error>(), Some(Naps2Error::InvalidOptionsError(_))), "{}", error);
+        
+        // Deskew runs first and fails on the page before Resample is reached
+        let deskew_first = ProcessingPipeline::new(vec![ProcessingStep::Deskew, ProcessingStep::Resample(150)]);
+        let error = deskew_first.apply(&no_helper(), scan(&["page.png"], &[], "")).unwrap_err();
+        assert!(!matches!(error.downcast_ref::<Naps2Error>(), Some(Naps2Error::InvalidOptionsError(_))), "{}", error);
+    }
+
+    #[test]
+    fn pages_and_their_dpis_keep_their_order() {
+        let names: Vec<String> = (0..40).map(|i| format!("page{}.png", i)).collect();
+        let pages: Vec<&str> = names.iter().map(String::as_str).collect();
+        let dpis: Vec<u32> = (0..40).map(|i| 100 + i).collect();
+        
+        let processed = ProcessingPipeline::default().apply(&no_helper(), scan(&pages, &dpis, "")).unwrap();
+        assert_eq!(processed.image_paths, names);
+        assert_eq!(processed.page_dpis, dpis);
+        
+        let processed = ProcessingPipeline::default().apply(&no_helper(), scan(&pages, &[], "")).unwrap();
+        assert!(processed.page_dpis.is_empty());
+    }
+
+    /// A helper that copies the input to `<input>-<subcommand>.png` and prints the new path
+    #[cfg(unix)]
+    fn copying_helper(dir: &Path) -> ImageClient {
+        use std::os::unix::fs::PermissionsExt;
+        
+        let helper = dir.join("helper.sh");
+        fs::write(&helper, "#!/bin/sh\nout=\"${3%.png}-$2.png\"\ncp \"$3\" \"$out\"\nprintf '\"%s\"' \"$out\"\n").unwrap();
+        fs::set_permissions(&helper, fs::Permissions::from_mode(0o755)).unwrap();
+        ImageClient::new(helper)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn intermediates_are_deleted_but_the_callers_pages_are_not() {
+        let dir = std::env::temp_dir().join(format!("naps2_pipeline_test_{}", std::process::id()));
+        let scan_dir = dir.join("scan");
+        fs::create_dir_all(&scan_dir).unwrap();
+        let images = copying_helper(&dir);
+        let (callers, scanned) = (dir.join("page.png"), scan_dir.join("page.png"));
+        for page in [&callers, &scanned] {
+            fs::write(page, b"\x89PNG\r\n\x1a\n\0\0\0\0").unwrap();
+        }
+        let pipeline = ProcessingPipeline::new(vec![ProcessingStep::Despeckle, ProcessingStep::Deskew]);
+        
+        let processed = pipeline.apply(&images, scan(&[callers.to_str().unwrap()], &[], "")).unwrap();
+        assert_eq!(processed.image_paths, [dir.join("page-despeckle-deskew.png").to_string_lossy()]);
+        assert!(callers.exists());
+        assert!(!dir.join("page-despeckle.png").exists());
+        
+        let processed = pipeline.apply(&images, scan(&[scanned.to_str().unwrap()], &[], scan_dir.to_str().unwrap())).unwrap();
+        assert_eq!(processed.image_paths, [scan_dir.join("page-despeckle-deskew.png").to_string_lossy()]);
+        assert!(!scanned.exists());
+        assert!(!scan_dir.join("page-despeckle.png").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
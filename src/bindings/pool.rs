@@ -0,0 +1,122 @@
+//! Shared client that bounds how many helper processes run at once
+
+use anyhow::Result;
+use std::path::PathBuf;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use crate::bindings::error::Naps2Error;
+use crate::bindings::Naps2Client;
+
+/// Largest pool size accepted by `Naps2Pool::new`
+pub const MAX_POOL_SIZE: usize = 64;
+
+/// What to do when work is submitted while every slot is busy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PoolPolicy {
+    /// Wait until a slot frees up
+    #[default]
+    Block,
+    /// Fail immediately with `Naps2Error::PoolExhausted`
+    Reject,
+}
+
+/// Snapshot of pool usage for monitoring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolMetrics {
+    /// Configured number of slots
+    pub size: usize,
+    /// Operations currently running
+    pub active: usize,
+    /// Callers waiting for a slot
+    pub queued: usize,
+}
+
+#[derive(Default)]
+struct PoolState {
+    active: usize,
+    queued: usize,
+}
+
+/// A `Naps2Client` shared between threads, running at most `size` operations at a time
+///
+/// Every operation starts its own helper process, so this caps the number of
+/// helpers a busy server can spawn.
+pub struct Naps2Pool {
+    client: Naps2Client,
+    size: usize,
+    policy: PoolPolicy,
+    state: Mutex<PoolState>,
+    available: Condvar,
+}
+
+/// Releases a pool slot when the operation finishes, even if it panics
+struct Slot<'a> {
+    pool: &'a Naps2Pool,
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.pool.lock().active -= 1;
+        self.pool.available.notify_one();
+    }
+}
+
+impl Naps2Pool {
+    /// Create a pool allowing `size` concurrent operations, up to `MAX_POOL_SIZE`
+    pub fn new(helper_path: PathBuf, size: usize, policy: PoolPolicy) -> Result<Self> {
+        if size == 0 || size > MAX_POOL_SIZE {
+            return Err(Naps2Error::InvalidOptionsError(format!(
+                "Pool size must be between 1 and {}, got {}", MAX_POOL_SIZE, size)).into());
+        }
+
+        Ok(Self {
+            client: Naps2Client::new(helper_path),
+            size,
+            policy,
+            state: Mutex::new(PoolState::default()),
+            available: Condvar::new(),
+        })
+    }
+
+    /// Run an operation on the shared client once a slot is available
+    pub fn run<T, F>(&self, operation: F) -> Result<T>
+    where
+        F: FnOnce(&Naps2Client) -> Result<T>,
+    {
+        let _slot = self.acquire()?;
+        operation(&self.client)
+    }
+
+    /// Current usage of the pool
+    pub fn metrics(&self) -> PoolMetrics {
+        let state = self.lock();
+        PoolMetrics {
+            size: self.size,
+            active: state.active,
+            queued: state.queued,
+        }
+    }
+
+    fn acquire(&self) -> Result<Slot<'_>> {
+        let mut state = self.lock();
+
+        if state.active >= self.size {
+            if self.policy == PoolPolicy::Reject {
+                return Err(Naps2Error::PoolExhausted(self.size).into());
+            }
+
+            state.queued += 1;
+            while state.active >= self.size {
+                state = self.available.wait(state).unwrap_or_else(|e| e.into_inner());
+            }
+            state.queued -= 1;
+        }
+
+        state.active += 1;
+        Ok(Slot { pool: self })
+    }
+
+    // The counters stay consistent even if an operation panicked, so ignore poisoning
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,90 @@
+//! Reading and writing the NAPS2 desktop app's recovery folder layout
+//!
+//! A recovery folder holds the page images of an unsaved batch plus an
+//! `index.xml` listing them in order. The desktop app offers to restore any
+//! such folder found in its recovery directory on startup.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Component, Path};
+use crate::bindings::error::Naps2Error;
+use crate::bindings::scan::ColorMode;
+
+/// Name of the index file inside a recovery folder
+pub(crate) const INDEX_FILE: &str = "index.xml";
+
+/// Bit depth name the desktop app records for each page
+fn bit_depth(color_mode: ColorMode) -> &'static str {
+    match color_mode {
+        ColorMode::Color => "C24Bit",
+        ColorMode::Grayscale => "Grayscale",
+        ColorMode::BlackAndWhite => "BlackWhite",
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Write `index.xml` listing `file_names` (relative to `folder`) in page order
+pub(crate) fn write_index(folder: &Path, file_names: &[String], color_mode: ColorMode) -> Result<()> {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<RecoveryIndex xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\">\n");
+    xml.push_str("  <Version>1</Version>\n");
+    xml.push_str("  <Images>\n");
+    for name in file_names {
+        xml.push_str("    <RecoveryIndexImage>\n");
+        xml.push_str(&format!("      <FileName>{}</FileName>\n", escape_xml(name)));
+        xml.push_str("      <TransformList />\n");
+        xml.push_str(&format!("      <BitDepth>{}</BitDepth>\n", bit_depth(color_mode)));
+        xml.push_str("      <HighQuality>false</HighQuality>\n");
+        xml.push_str("    </RecoveryIndexImage>\n");
+    }
+    xml.push_str("  </Images>\n");
+    xml.push_str("</RecoveryIndex>\n");
+
+    let path = folder.join(INDEX_FILE);
+    fs::write(&path, xml).with_context(|| format!("Failed to write recovery index {:?}", path))
+}
+
+/// Read the page file names listed in a folder's `index.xml`, in page order
+pub(crate) fn read_index(folder: &Path) -> Result<Vec<String>> {
+    let path = folder.join(INDEX_FILE);
+    let xml = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read recovery index {:?}", path))?;
+
+    if !xml.contains("<RecoveryIndex") {
+        return Err(Naps2Error::ScanningError(format!("Not a NAPS2 recovery index: {:?}", path)).into());
+    }
+
+    let mut names = Vec::new();
+    let mut rest = xml.as_str();
+    while let Some(start) = rest.find("<FileName>") {
+        rest = &rest[start + "<FileName>".len()..];
+        let end = rest.find("</FileName>").ok_or_else(|| Naps2Error::ScanningError(
+            format!("Unterminated FileName in recovery index {:?}", path)))?;
+        let name = unescape_xml(rest[..end].trim());
+        
+        // Pages live directly in the folder, so anything else could point outside it
+        let mut components = Path::new(&name).components();
+        if !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) {
+            return Err(Naps2Error::ScanningError(
+                format!("Invalid FileName {:?} in recovery index {:?}", name, path)).into());
+        }
+        names.push(name);
+        rest = &rest[end..];
+    }
+
+    Ok(names)
+}
@@ -0,0 +1,415 @@
+//! In-place editing of the resolution stored in image files
+//!
+//! Only metadata is rewritten; pixel data is copied byte for byte.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use crate::bindings::error::Naps2Error;
+use crate::bindings::images::ImageFormat;
+
+const TIFF_X_RESOLUTION: u16 = 282;
+const TIFF_Y_RESOLUTION: u16 = 283;
+const TIFF_RESOLUTION_UNIT: u16 = 296;
+const TIFF_TYPE_SHORT: u16 = 3;
+const TIFF_TYPE_RATIONAL: u16 = 5;
+const TIFF_UNIT_INCH: u16 = 2;
+
+fn image_error(path: &Path, reason: &str) -> anyhow::Error {
+    Naps2Error::ImageError(format!("Cannot set DPI of {:?}: {}", path, reason)).into()
+}
+
+/// CRC-32 as used by PNG chunks
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Byte order of a TIFF structure
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    pub(crate) fn u16(self, data: &[u8], pos: usize) -> Option<u16> {
+        let b = data.get(pos..pos + 2)?;
+        Some(match self {
+            ByteOrder::Little => u16::from_le_bytes([b[0], b[1]]),
+            ByteOrder::Big => u16::from_be_bytes([b[0], b[1]]),
+        })
+    }
+
+    pub(crate) fn u32(self, data: &[u8], pos: usize) -> Option<u32> {
+        let b = data.get(pos..pos + 4)?;
+        Some(match self {
+            ByteOrder::Little => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            ByteOrder::Big => u32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+        })
+    }
+
+    fn put_u16(self, data: &mut [u8], pos: usize, value: u16) {
+        let bytes = match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        };
+        data[pos..pos + 2].copy_from_slice(&bytes);
+    }
+
+    fn put_u32(self, data: &mut [u8], pos: usize, value: u32) {
+        let bytes = match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        };
+        data[pos..pos + 4].copy_from_slice(&bytes);
+    }
+}
+
+/// Overwrite the resolution tags of every IFD in a TIFF structure
+///
+/// Returns whether any resolution tag was found. Tags can't be added without
+/// rewriting the IFD, so files lacking them are left untouched.
+fn patch_tiff(data: &mut [u8], dpi: u32) -> bool {
+    let order = match data.get(0..2) {
+        Some(b"II") => ByteOrder::Little,
+        Some(b"MM") => ByteOrder::Big,
+        _ => return false,
+    };
+
+    let mut found = false;
+    let mut ifd = order.u32(data, 4).unwrap_or(0) as usize;
+    let mut visited = Vec::new();
+
+    while ifd != 0 && !visited.contains(&ifd) {
+        visited.push(ifd);
+        let Some(count) = order.u16(data, ifd) else { break };
+
+        for i in 0..count as usize {
+            let entry = ifd + 2 + i * 12;
+            let (Some(tag), Some(field_type), Some(value)) =
+                (order.u16(data, entry), order.u16(data, entry + 2), order.u32(data, entry + 8)) else { break };
+
+            match (tag, field_type) {
+                (TIFF_X_RESOLUTION | TIFF_Y_RESOLUTION, TIFF_TYPE_RATIONAL) => {
+                    let offset = value as usize;
+                    if offset + 8 <= data.len() {
+                        order.put_u32(data, offset, dpi);
+                        order.put_u32(data, offset + 4, 1);
+                        found = true;
+                    }
+                }
+                (TIFF_RESOLUTION_UNIT, TIFF_TYPE_SHORT) => order.put_u16(data, entry + 8, TIFF_UNIT_INCH),
+                _ => {}
+            }
+        }
+
+        ifd = order.u32(data, ifd + 2 + count as usize * 12).unwrap_or(0) as usize;
+    }
+
+    found
+}
+
+/// Update the JFIF density and any EXIF resolution tags of a JPEG
+fn set_jpeg_dpi(path: &Path, mut data: Vec<u8>, dpi: u32) -> Result<Vec<u8>> {
+    let density = u16::try_from(dpi).map_err(|_| image_error(path, "JPEG density is limited to 65535"))?;
+    let mut pos = 2;
+    let mut has_jfif = false;
+
+    // Metadata segments all come before the start of scan
+    while pos + 4 <= data.len() && data[pos] == 0xFF {
+        let marker = data[pos + 1];
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        if length < 2 {
+            return Err(image_error(path, "corrupt JPEG segment"));
+        }
+        let end = (pos + 2 + length).min(data.len());
+        let segment = &mut data[pos + 4..end];
+
+        if marker == 0xE0 && segment.starts_with(b"JFIF\0") && segment.len() >= 12 {
+            segment[7] = 1;
+            segment[8..10].copy_from_slice(&density.to_be_bytes());
+            segment[10..12].copy_from_slice(&density.to_be_bytes());
+            has_jfif = true;
+        } else if marker == 0xE1 && segment.starts_with(b"Exif\0\0") {
+            patch_tiff(&mut segment[6..], dpi);
+        }
+
+        pos += 2 + length;
+    }
+
+    if !has_jfif {
+        // Insert a JFIF header straight after the start-of-image marker
+        let mut app0 = vec![0xFF, 0xE0, 0x00, 0x10];
+        app0.extend_from_slice(b"JFIF\0\x01\x02\x01");
+        app0.extend_from_slice(&density.to_be_bytes());
+        app0.extend_from_slice(&density.to_be_bytes());
+        app0.extend_from_slice(&[0, 0]);
+        data.splice(2..2, app0);
+    }
+
+    Ok(data)
+}
+
+/// Replace or insert the pHYs chunk of a PNG
+fn set_png_dpi(path: &Path, data: &[u8], dpi: u32) -> Result<Vec<u8>> {
+    let pixels_per_metre = (dpi as f64 / 0.0254).round() as u32;
+    let mut phys = b"pHYs".to_vec();
+    phys.extend_from_slice(&pixels_per_metre.to_be_bytes());
+    phys.extend_from_slice(&pixels_per_metre.to_be_bytes());
+    phys.push(1);
+
+    let mut chunk = 9u32.to_be_bytes().to_vec();
+    chunk.extend_from_slice(&phys);
+    chunk.extend_from_slice(&crc32(&phys).to_be_bytes());
+
+    let mut output = data[..8].to_vec();
+    let mut pos = 8;
+    let mut written = false;
+
+    while pos + 8 <= data.len() {
+        let length = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+        let end = pos + 12 + length;
+        if end > data.len() {
+            return Err(image_error(path, "truncated PNG"));
+        }
+        let chunk_type = &data[pos + 4..pos + 8];
+
+        if chunk_type == b"pHYs" {
+            if !written {
+                output.extend_from_slice(&chunk);
+                written = true;
+            }
+        } else {
+            output.extend_from_slice(&data[pos..end]);
+            if chunk_type == b"IHDR" && !written {
+                output.extend_from_slice(&chunk);
+                written = true;
+            }
+        }
+
+        pos = end;
+    }
+
+    Ok(output)
+}
+
+/// Rewrite the resolution recorded in a JPEG, PNG or TIFF file
+pub(crate) fn set_dpi(path: &Path, dpi: u32) -> Result<()> {
+    if dpi == 0 {
+        return Err(image_error(path, "DPI must be greater than zero"));
+    }
+
+    let data = fs::read(path).with_context(|| format!("Failed to read image {:?}", path))?;
+
+    let updated = match ImageFormat::from_magic_bytes(&data) {
+        Some(ImageFormat::Jpeg) => set_jpeg_dpi(path, data, dpi)?,
+        Some(ImageFormat::Png) => set_png_dpi(path, &data, dpi)?,
+        Some(ImageFormat::Tiff) => {
+            let mut data = data;
+            if !patch_tiff(&mut data, dpi) {
+                return Err(image_error(path, "TIFF has no resolution tags to update"));
+            }
+            data
+        }
+        _ => return Err(image_error(path, "only JPEG, PNG and TIFF are supported")),
+    };
+
+    fs::write(path, updated).with_context(|| format!("Failed to write image {:?}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Write `data` to a fresh temp file, set its DPI and read the result back
+    fn set_dpi_of(name: &str, data: &[u8], dpi: u32) -> Result<Vec<u8>> {
+        let path: PathBuf = std::env::temp_dir().join(format!("naps2-resolution-{}-{}", std::process::id(), name));
+        fs::write(&path, data).unwrap();
+        let result = set_dpi(&path, dpi).map(|()| fs::read(&path).unwrap());
+        let _ = fs::remove_file(&path);
+        result
+    }
+
+    fn jpeg(segments: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8];
+        for (marker, payload) in segments {
+            data.extend_from_slice(&[0xFF, *marker]);
+            data.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+            data.extend_from_slice(payload);
+        }
+        data.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9]);
+        data
+    }
+
+    /// JFIF density unit and X/Y densities of a JPEG
+    fn jfif_density(data: &[u8]) -> Option<(u8, u16, u16)> {
+        let mut pos = 2;
+        while pos + 4 <= data.len() && data[pos] == 0xFF && data[pos + 1] != 0xDA {
+            let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+            let segment = &data[pos + 4..pos + 2 + length];
+            if data[pos + 1] == 0xE0 && segment.starts_with(b"JFIF\0") {
+                return Some((segment[7],
+                             u16::from_be_bytes([segment[8], segment[9]]),
+                             u16::from_be_bytes([segment[10], segment[11]])));
+            }
+            pos += 2 + length;
+        }
+        None
+    }
+
+    fn png_chunk(chunk_type: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut body = chunk_type.to_vec();
+        body.extend_from_slice(payload);
+        let mut chunk = (payload.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(&body);
+        chunk.extend_from_slice(&crc32(&body).to_be_bytes());
+        chunk
+    }
+
+    fn png(extra: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+        data.extend(png_chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]));
+        for chunk in extra {
+            data.extend_from_slice(chunk);
+        }
+        data.extend(png_chunk(b"IEND", &[]));
+        data
+    }
+
+    /// Chunk types in order, and the pixels per metre and unit of every pHYs chunk
+    fn png_phys(data: &[u8]) -> (Vec<String>, Vec<(u32, u8)>) {
+        let (mut types, mut phys) = (Vec::new(), Vec::new());
+        let mut pos = 8;
+        while pos + 12 <= data.len() {
+            let length = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+            let body = &data[pos + 4..pos + 8 + length];
+            let crc = u32::from_be_bytes(data[pos + 8 + length..pos + 12 + length].try_into().unwrap());
+            assert_eq!(crc, crc32(body), "bad CRC");
+            types.push(String::from_utf8_lossy(&body[..4]).to_string());
+            if &body[..4] == b"pHYs" {
+                phys.push((u32::from_be_bytes(body[4..8].try_into().unwrap()), body[12]));
+            }
+            pos += 12 + length;
+        }
+        (types, phys)
+    }
+
+    /// A little-endian TIFF with 72 DPI resolution tags in centimetres
+    fn tiff() -> Vec<u8> {
+        let mut data = b"II*\0".to_vec();
+        data.extend_from_slice(&8u32.to_le_bytes());
+        data.extend_from_slice(&3u16.to_le_bytes());
+        let rationals = 8 + 2 + 3 * 12 + 4;
+        for (tag, field_type, value) in [
+            (TIFF_X_RESOLUTION, TIFF_TYPE_RATIONAL, rationals as u32),
+            (TIFF_Y_RESOLUTION, TIFF_TYPE_RATIONAL, rationals as u32 + 8),
+            (TIFF_RESOLUTION_UNIT, TIFF_TYPE_SHORT, 3),
+        ] {
+            data.extend_from_slice(&tag.to_le_bytes());
+            data.extend_from_slice(&field_type.to_le_bytes());
+            data.extend_from_slice(&1u32.to_le_bytes());
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&0u32.to_le_bytes());
+        for _ in 0..2 {
+            data.extend_from_slice(&72u32.to_le_bytes());
+            data.extend_from_slice(&1u32.to_le_bytes());
+        }
+        data
+    }
+
+    /// X and Y resolution as numerator/denominator, and the resolution unit, of the first IFD
+    fn tiff_resolution(data: &[u8]) -> ((u32, u32), (u32, u32), u16) {
+        let order = ByteOrder::Little;
+        let ifd = order.u32(data, 4).unwrap() as usize;
+        let (mut x, mut y, mut unit) = ((0, 0), (0, 0), 0);
+        for i in 0..order.u16(data, ifd).unwrap() as usize {
+            let entry = ifd + 2 + i * 12;
+            let value = order.u32(data, entry + 8).unwrap() as usize;
+            let rational = || (order.u32(data, value).unwrap(), order.u32(data, value + 4).unwrap());
+            match order.u16(data, entry).unwrap() {
+                TIFF_X_RESOLUTION => x = rational(),
+                TIFF_Y_RESOLUTION => y = rational(),
+                TIFF_RESOLUTION_UNIT => unit = order.u16(data, entry + 8).unwrap(),
+                _ => {}
+            }
+        }
+        (x, y, unit)
+    }
+
+    #[test]
+    fn jpeg_jfif_density_is_updated() {
+        let original = jpeg(&[(0xE0, b"JFIF\0\x01\x02\x00\x00\x48\x00\x48\x00\x00")]);
+        let updated = set_dpi_of("jfif.jpg", &original, 300).unwrap();
+        assert_eq!(jfif_density(&updated), Some((1, 300, 300)));
+        assert_eq!(updated.len(), original.len());
+        assert!(updated.ends_with(&[0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9]));
+    }
+
+    #[test]
+    fn jpeg_without_jfif_gets_one() {
+        let original = jpeg(&[(0xFE, b"comment")]);
+        let updated = set_dpi_of("nojfif.jpg", &original, 150).unwrap();
+        assert_eq!(jfif_density(&updated), Some((1, 150, 150)));
+        assert!(updated.ends_with(&original[2..]));
+    }
+
+    #[test]
+    fn jpeg_exif_resolution_is_updated() {
+        let mut exif = b"Exif\0\0".to_vec();
+        exif.extend(tiff());
+        let original = jpeg(&[(0xE1, &exif)]);
+        let updated = set_dpi_of("exif.jpg", &original, 600).unwrap();
+        // The JFIF segment is inserted ahead of the EXIF one
+        let exif_start = 2 + 18 + 4 + 6;
+        assert_eq!(tiff_resolution(&updated[exif_start..]), ((600, 1), (600, 1), TIFF_UNIT_INCH));
+    }
+
+    #[test]
+    fn jpeg_segment_with_short_length_is_an_error() {
+        let mut original = jpeg(&[(0xFE, b"comment")]);
+        original[4..6].copy_from_slice(&1u16.to_be_bytes());
+        let error = set_dpi_of("corrupt.jpg", &original, 300).unwrap_err();
+        assert!(error.to_string().contains("corrupt JPEG segment"), "{}", error);
+    }
+
+    #[test]
+    fn png_phys_is_inserted_after_ihdr() {
+        let updated = set_dpi_of("new.png", &png(&[]), 300).unwrap();
+        let (types, phys) = png_phys(&updated);
+        assert_eq!(types, ["IHDR", "pHYs", "IEND"]);
+        assert_eq!(phys, [(11811, 1)]);
+    }
+
+    #[test]
+    fn png_phys_is_replaced() {
+        let old = png_chunk(b"pHYs", &[0, 0, 0x0B, 0x13, 0, 0, 0x0B, 0x13, 1]);
+        let updated = set_dpi_of("old.png", &png(&[png_chunk(b"tEXt", b"a\0b"), old]), 96).unwrap();
+        let (types, phys) = png_phys(&updated);
+        assert_eq!(types, ["IHDR", "pHYs", "tEXt", "IEND"]);
+        assert_eq!(phys, [(3780, 1)]);
+    }
+
+    #[test]
+    fn tiff_resolution_tags_are_updated() {
+        let updated = set_dpi_of("page.tiff", &tiff(), 200).unwrap();
+        assert_eq!(tiff_resolution(&updated), ((200, 1), (200, 1), TIFF_UNIT_INCH));
+    }
+
+    #[test]
+    fn zero_dpi_is_rejected() {
+        assert!(set_dpi_of("zero.png", &png(&[]), 0).is_err());
+    }
+}
@@ -2,9 +2,23 @@
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use crate::bindings::device_lock::{DeviceLock, DeviceLockGuard};
 use crate::bindings::error::Naps2Error;
+use crate::bindings::helper;
+use crate::bindings::images::{AutoFormat, ImageClient, ImageFormat, Rect};
+use crate::bindings::naming::{unique_path, NamingContext, OutputNaming, OverwritePolicy, SavedFile, TemplateNaming};
+use crate::bindings::pdf::iso8601;
+use crate::bindings::recovery;
+use crate::bindings::sink::PageSink;
 
 /// Supported scanner drivers
 #[derive(Debug, Clone, Copy)]
@@ -30,18 +44,57 @@ impl Driver {
         }
     }
     
+    /// Parse a driver name as reported by the C# helper
+    pub fn from_name(name: &str) -> Option<Driver> {
+        [Driver::Default, Driver::Apple, Driver::Sane, Driver::Escl, Driver::Wia, Driver::Twain]
+            .into_iter()
+            .find(|d| d.to_string().eq_ignore_ascii_case(name))
+    }
+    
     /// Get all drivers available on macOS
     pub fn mac_drivers() -> Vec<Driver> {
         vec![Driver::Default, Driver::Apple, Driver::Sane, Driver::Escl]
     }
 }
 
+/// Kind of page identified by `ScanClient::scan_auto_detect`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentType {
+    /// Black text on a light background
+    Text,
+    /// Photos, illustrations or other colored or continuous-tone content
+    Photo,
+    /// A small card, scanned cropped to its size
+    BusinessCard,
+}
+
+/// Resolution of the preview scan used by auto-detection
+const PREVIEW_DPI: u32 = 75;
+
+/// Content smaller than this in both directions, in millimetres, counts as a card
+const CARD_MAX_MM: f64 = 100.0;
+
+/// Result of `ScanClient::scan_auto_detect`
+#[derive(Debug)]
+pub struct AutoDetectedScan {
+    pub document_type: DocumentType,
+    /// Settings used for the full scan
+    pub options: ScanOptions,
+    pub result: ScanResult,
+}
+
 /// Paper source for scanning
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PaperSource {
     Flatbed,
     Feeder,
     Duplex,
+    /// The feeder if paper is loaded in it, otherwise the flatbed
+    ///
+    /// Decided with `ScanClient::feeder_has_paper` when the scan starts; the
+    /// choice is reported in `ScanResult::paper_source`. If the feeder can't
+    /// tell whether it has paper, the flatbed is used.
+    Auto,
 }
 
 impl PaperSource {
@@ -51,12 +104,292 @@ impl PaperSource {
             PaperSource::Flatbed => "Flatbed",
             PaperSource::Feeder => "Feeder",
             PaperSource::Duplex => "Duplex",
+            PaperSource::Auto => "Auto",
+        }
+    }
+    
+    /// Parse a paper source name as reported by the C# helper
+    pub fn from_name(name: &str) -> Option<PaperSource> {
+        [PaperSource::Flatbed, PaperSource::Feeder, PaperSource::Duplex]
+            .into_iter()
+            .find(|s| s.to_string().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Color mode for scanning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    #[default]
+    Color,
+    Grayscale,
+    BlackAndWhite,
+}
+
+impl ColorMode {
+    /// Convert to string for passing to the C# helper
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            ColorMode::Color => "Color",
+            ColorMode::Grayscale => "Grayscale",
+            ColorMode::BlackAndWhite => "BlackAndWhite",
+        }
+    }
+    
+    /// Uncompressed bytes needed per pixel in this mode
+    pub fn bytes_per_pixel(&self) -> f64 {
+        match self {
+            ColorMode::Color => 3.0,
+            ColorMode::Grayscale => 1.0,
+            ColorMode::BlackAndWhite => 0.125,
+        }
+    }
+}
+
+/// Page size for scanning
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PageSize {
+    Letter,
+    Legal,
+    A4,
+    A3,
+    Custom { width_mm: f64, height_mm: f64 },
+}
+
+impl PageSize {
+    /// Page width and height in millimetres
+    pub fn dimensions_mm(&self) -> (f64, f64) {
+        match self {
+            PageSize::Letter => (215.9, 279.4),
+            PageSize::Legal => (215.9, 355.6),
+            PageSize::A4 => (210.0, 297.0),
+            PageSize::A3 => (297.0, 420.0),
+            PageSize::Custom { width_mm, height_mm } => (*width_mm, *height_mm),
+        }
+    }
+    
+    /// Convert to string for passing to the C# helper
+    pub fn to_arg(&self) -> String {
+        match self {
+            PageSize::Letter => "Letter".to_string(),
+            PageSize::Legal => "Legal".to_string(),
+            PageSize::A4 => "A4".to_string(),
+            PageSize::A3 => "A3".to_string(),
+            PageSize::Custom { width_mm, height_mm } => format!("{}x{}mm", width_mm, height_mm),
+        }
+    }
+}
+
+/// Pixel count above which scans are refused when `ScanOptions::max_megapixels` is unset
+///
+/// 150 megapixels is roughly A3 at 900 DPI, or ~450 MB of uncompressed color.
+pub const DEFAULT_MAX_MEGAPIXELS: f64 = 150.0;
+
+/// Valid range for brightness and contrast adjustments, matching NAPS2
+pub const ADJUSTMENT_RANGE: std::ops::RangeInclusive<i32> = -1000..=1000;
+
+/// Check that a brightness or contrast value is within `ADJUSTMENT_RANGE`
+pub(crate) fn check_adjustment(name: &str, value: i32) -> Result<()> {
+    if !ADJUSTMENT_RANGE.contains(&value) {
+        return Err(Naps2Error::InvalidOptionsError(format!(
+            "{} must be between {} and {}, got {}",
+            name, ADJUSTMENT_RANGE.start(), ADJUSTMENT_RANGE.end(), value)).into());
+    }
+    Ok(())
+}
+
+/// Connection settings for network scanners reached through `Driver::Escl`
+#[derive(Debug, Clone, Default)]
+pub struct EsclOptions {
+    /// Connect over HTTPS instead of plain HTTP
+    pub https: bool,
+    /// Accept a self-signed or otherwise untrusted server certificate
+    ///
+    /// Common on office scanners, which ship with a self-signed certificate.
+    /// The connection is still encrypted but the scanner's identity isn't checked.
+    pub accept_invalid_certificates: bool,
+    /// PEM client certificate, for scanners that require mutual TLS
+    pub client_certificate: Option<PathBuf>,
+    /// PEM private key for `client_certificate`, if it isn't in the same file
+    pub client_key: Option<PathBuf>,
+}
+
+/// Options for a scan
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Driver used to reach the device, or the helper's default
+    pub driver: Option<Driver>,
+    /// Scan resolution in dots per inch
+    pub dpi: u32,
+    /// Paper source, or the device's default
+    pub paper_source: Option<PaperSource>,
+    /// Page size, or the device's default (estimated as A4)
+    pub page_size: Option<PageSize>,
+    /// Color mode of the scanned images
+    pub color_mode: ColorMode,
+    /// Largest page the crate will request, in megapixels
+    ///
+    /// `None` applies `DEFAULT_MAX_MEGAPIXELS`; use `Some(f64::INFINITY)` to disable the guard.
+    pub max_megapixels: Option<f64>,
+    /// Black/white cutoff (0-255) for `ColorMode::BlackAndWhite`
+    ///
+    /// Raising it keeps more faint strokes black. `None` lets the driver choose.
+    pub bw_threshold: Option<u8>,
+    /// Gamma applied before thresholding in `ColorMode::BlackAndWhite`
+    ///
+    /// Values above 1.0 darken midtones so faint text survives binarization.
+    pub gamma: Option<f32>,
+    /// Brightness adjustment in `ADJUSTMENT_RANGE`, 0 for none
+    pub brightness: i32,
+    /// Contrast adjustment in `ADJUSTMENT_RANGE`, 0 for none
+    pub contrast: i32,
+    /// Show the TWAIN driver's own scan dialog instead of scanning headlessly
+    ///
+    /// Only supported with `Driver::Twain` on Windows. Settings chosen in the
+    /// dialog take precedence over the other options here.
+    pub show_native_ui: bool,
+    /// Reverse the returned page order, for feeders that pull the last page first
+    ///
+    /// Applied by `scan`, `resume_or_scan` and the methods built on them.
+    /// `scan_stream` yields pages as they arrive and ignores this;
+    /// `scan_to_sink` can't reorder pages it has already handed over, and
+    /// rejects it.
+    pub reverse_order: bool,
+    /// Pick DPI, color mode and page size from a quick preview scan
+    ///
+    /// Overrides `dpi`, `color_mode` and `page_size`; see `ScanClient::scan_auto_detect`.
+    /// Only supported on the flatbed, since a preview would feed the page through.
+    pub auto_detect: bool,
+    /// Scan from the flatbed if the device lacks the requested `paper_source`
+    ///
+    /// Without this, `ScanClient::scan` fails with `Naps2Error::UnsupportedPaperSource`.
+    /// A fallback is noted in `ScanResult::warnings`.
+    pub fallback_to_flatbed: bool,
+    /// Return an empty result instead of failing when no pages were scanned
+    ///
+    /// By default `ScanClient::scan`, `resume_or_scan`, `scan_to_sink` and
+    /// `scan_stream` fail with `Naps2Error::NoPagesScanned`, e.g. for an empty
+    /// feeder, so an empty batch can't be exported by mistake. A resumed scan
+    /// only fails if the checkpoint directory holds no pages from any session.
+    pub allow_empty: bool,
+    /// HTTPS and client certificate settings, only used with `Driver::Escl`
+    ///
+    /// A failed TLS handshake is reported as `Naps2Error::TlsError`.
+    pub escl: Option<EsclOptions>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            driver: None,
+            dpi: 300,
+            paper_source: None,
+            page_size: None,
+            color_mode: ColorMode::default(),
+            max_megapixels: None,
+            bw_threshold: None,
+            gamma: None,
+            brightness: 0,
+            contrast: 0,
+            show_native_ui: false,
+            reverse_order: false,
+            auto_detect: false,
+            fallback_to_flatbed: false,
+            allow_empty: false,
+            escl: None,
+        }
+    }
+}
+
+impl ScanOptions {
+    /// Estimated size of one scanned page in megapixels
+    pub fn estimated_megapixels(&self) -> f64 {
+        let (width_mm, height_mm) = self.page_size.unwrap_or(PageSize::A4).dimensions_mm();
+        let dpi = self.dpi as f64;
+        (width_mm / 25.4 * dpi) * (height_mm / 25.4 * dpi) / 1_000_000.0
+    }
+    
+    /// Rough size of the saved images for `page_count` pages, in bytes
+    ///
+    /// Starts from the uncompressed size implied by the DPI, page size (A4 if
+    /// unset) and color mode, then assumes typical compression for a scanned
+    /// document page: about 10:1 for color and 8:1 for grayscale JPEG, and
+    /// 10:1 for black and white. Real sizes vary with content, so treat the
+    /// result as an order of magnitude for warnings, not a limit.
+    pub fn estimate_output_bytes(&self, page_count: usize) -> u64 {
+        let compression = match self.color_mode {
+            ColorMode::Color => 10.0,
+            ColorMode::Grayscale => 8.0,
+            ColorMode::BlackAndWhite => 10.0,
+        };
+        let page_bytes = self.estimated_megapixels() * 1_000_000.0 * self.color_mode.bytes_per_pixel() / compression;
+        (page_bytes * page_count as f64).round() as u64
+    }
+    
+    /// Check that the options are consistent and within the configured size limit
+    pub fn validate(&self) -> Result<()> {
+        if self.color_mode != ColorMode::BlackAndWhite
+            && (self.bw_threshold.is_some() || self.gamma.is_some()) {
+            return Err(Naps2Error::InvalidOptionsError(
+                "bw_threshold and gamma only apply to ColorMode::BlackAndWhite".to_string()).into());
+        }
+        if let Some(gamma) = self.gamma {
+            if !(gamma > 0.0 && gamma.is_finite()) {
+                return Err(Naps2Error::InvalidOptionsError(
+                    format!("gamma must be a positive number, got {}", gamma)).into());
+            }
+        }
+        check_adjustment("brightness", self.brightness)?;
+        check_adjustment("contrast", self.contrast)?;
+        if self.auto_detect && matches!(self.paper_source, Some(PaperSource::Feeder | PaperSource::Duplex | PaperSource::Auto)) {
+            return Err(Naps2Error::InvalidOptionsError(
+                "auto_detect needs a flatbed preview and can't be used with the feeder".to_string()).into());
         }
+        if self.show_native_ui && !(cfg!(target_os = "windows") && matches!(self.driver, Some(Driver::Twain))) {
+            return Err(Naps2Error::InvalidOptionsError(
+                "show_native_ui requires the TWAIN driver on Windows".to_string()).into());
+        }
+        if let Some(escl) = &self.escl {
+            if !matches!(self.driver, Some(Driver::Escl)) {
+                return Err(Naps2Error::InvalidOptionsError(
+                    "escl options require the ESCL driver".to_string()).into());
+            }
+            if (escl.client_certificate.is_some() || escl.client_key.is_some()) && !escl.https {
+                return Err(Naps2Error::InvalidOptionsError(
+                    "A client certificate can only be used over HTTPS".to_string()).into());
+            }
+            if escl.client_key.is_some() && escl.client_certificate.is_none() {
+                return Err(Naps2Error::InvalidOptionsError(
+                    "client_key was given without client_certificate".to_string()).into());
+            }
+            for path in escl.client_certificate.iter().chain(&escl.client_key) {
+                if !path.is_file() {
+                    return Err(Naps2Error::InvalidOptionsError(
+                        format!("Client certificate file {:?} does not exist", path)).into());
+                }
+            }
+        }
+        
+        self.check_limits()
+    }
+    
+    /// Check the requested page against the configured size limit
+    pub fn check_limits(&self) -> Result<()> {
+        let limit = self.max_megapixels.unwrap_or(DEFAULT_MAX_MEGAPIXELS);
+        let megapixels = self.estimated_megapixels();
+        
+        if megapixels > limit {
+            let bytes = megapixels * 1_000_000.0 * self.color_mode.bytes_per_pixel();
+            return Err(Naps2Error::ScanTooLargeError(format!(
+                "{:.0} megapixels (~{:.0} MB uncompressed) at {} DPI exceeds limit of {:.0} megapixels",
+                megapixels, bytes / 1_000_000.0, self.dpi, limit)).into());
+        }
+        
+        Ok(())
     }
 }
 
 /// Scanner device information
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct ScannerDevice {
     #[serde(rename = "Id")]
     pub id: String,
@@ -64,6 +397,60 @@ pub struct ScannerDevice {
     pub name: String,
     #[serde(rename = "Driver")]
     pub driver: String,
+    /// Identifies the physical scanner whichever driver found it, if the helper could tell
+    ///
+    /// A network host or UUID, e.g. `host:192.168.1.20`. Used by
+    /// `ScanClient::get_devices_concurrent` to merge drivers' lists.
+    #[serde(rename = "Key", default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// The same scanner as reported by other drivers, each usable with its own ID
+    #[serde(rename = "Alternates", default, skip_serializing_if = "Vec::is_empty")]
+    pub alternates: Vec<ScannerDevice>,
+}
+
+/// Identifying details a scanner reports about itself
+///
+/// Fields are `None` when the driver doesn't expose them or the device doesn't report them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DeviceInfo {
+    #[serde(rename = "Manufacturer", default)]
+    pub manufacturer: Option<String>,
+    #[serde(rename = "Model", default)]
+    pub model: Option<String>,
+    #[serde(rename = "SerialNumber", default)]
+    pub serial_number: Option<String>,
+    #[serde(rename = "FirmwareVersion", default)]
+    pub firmware_version: Option<String>,
+}
+
+/// Readiness of a known scanning device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum DeviceStatus {
+    Ready,
+    Busy,
+    Offline,
+    Warming,
+}
+
+/// Status response returned by the helper
+#[derive(Debug, Deserialize)]
+struct DeviceStatusResponse {
+    #[serde(rename = "Status")]
+    status: DeviceStatus,
+}
+
+/// Feeder sensor response returned by the helper
+#[derive(Debug, Deserialize)]
+struct FeederResponse {
+    #[serde(rename = "HasPaper", default)]
+    has_paper: Option<bool>,
+}
+
+/// Paper sources response returned by the helper
+#[derive(Debug, Deserialize)]
+struct SourcesResponse {
+    #[serde(rename = "Sources", default)]
+    sources: Vec<String>,
 }
 
 /// Result of a scanning operation
@@ -73,22 +460,452 @@ pub struct ScanResult {
     pub image_paths: Vec<String>,
     #[serde(rename = "TempDirectory")]
     pub temp_directory: String,
+    /// Problems that didn't stop the scan, such as falling back to the flatbed
+    #[serde(rename = "Warnings", default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Source the pages were scanned from, if one was requested
+    ///
+    /// Shows the choice made for `PaperSource::Auto` or a fallback to the flatbed.
+    #[serde(skip)]
+    pub paper_source: Option<PaperSource>,
+    /// Resolution each page was actually scanned at, in the same order as `image_paths`
+    ///
+    /// Drivers with automatic resolution can differ from the requested DPI, or
+    /// from page to page. Pages the driver didn't report are given the requested
+    /// DPI. Empty for results assembled from files, such as a recovery folder.
+    #[serde(rename = "PageDpis", default, skip_serializing_if = "Vec::is_empty")]
+    pub page_dpis: Vec<u32>,
+    /// How long the scan took, for results of `ScanClient::scan` and `scan_to_images`
+    #[serde(skip)]
+    pub timing: Option<ScanTiming>,
+}
+
+/// How long a scan took, as measured by `ScanClient::scan` and `scan_to_images`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanTiming {
+    /// Time from starting the helper until it exited, including device warm-up
+    pub total: Duration,
+    /// Time the driver spent on each page, in the same order as `image_paths`
+    ///
+    /// Empty when the driver doesn't report page events. A page far slower
+    /// than the rest usually means a feeder stall or a retried pick.
+    pub pages: Vec<Duration>,
+}
+
+/// Per-page timing reported alongside a scan result
+#[derive(Debug, Deserialize)]
+struct PageTimesResponse {
+    #[serde(rename = "PageTimesMs", default)]
+    page_times_ms: Vec<u64>,
+}
+
+impl ScanTiming {
+    /// Combine the measured `total` with the page times in the helper's output
+    fn from_output(stdout: &str, total: Duration) -> Result<Self> {
+        let response: PageTimesResponse = helper::parse_json(stdout)?;
+        Ok(Self {
+            total,
+            pages: response.page_times_ms.into_iter().map(Duration::from_millis).collect(),
+        })
+    }
+}
+
+impl ScanResult {
+    /// Resolution of the page at `index`, if known
+    pub fn page_dpi(&self, index: usize) -> Option<u32> {
+        self.page_dpis.get(index).copied()
+    }
+    
+    /// Give every page without a reported resolution the requested `dpi`
+    fn fill_page_dpis(&mut self, dpi: u32) {
+        self.page_dpis.resize(self.image_paths.len(), dpi);
+    }
+    
+    /// Reverse the page order, keeping each page's DPI and timing with it
+    fn reverse_pages(&mut self) {
+        self.image_paths.reverse();
+        self.page_dpis.reverse();
+        if let Some(timing) = &mut self.timing {
+            timing.pages.reverse();
+        }
+    }
+}
+
+/// List the `page<N>.<ext>` files in a checkpoint directory in page order
+fn checkpoint_pages(dir: &Path) -> Result<Vec<String>> {
+    let mut pages = Vec::new();
+    
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read directory {:?}", dir))? {
+        let path = entry?.path();
+        let number = path.file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.strip_prefix("page"))
+            .and_then(|n| n.parse::<usize>().ok());
+        
+        if let Some(number) = number {
+            pages.push((number, path.to_string_lossy().to_string()));
+        }
+    }
+    
+    pages.sort();
+    Ok(pages.into_iter().map(|(_, path)| path).collect())
+}
+
+/// How to recognize the same physical scanner under different drivers
+#[derive(Debug, Clone)]
+pub enum DeviceMatcher {
+    /// Exact device ID (only stable within one driver)
+    Id(String),
+    /// Device name, compared case-insensitively
+    Name(String),
+    /// Case-insensitive substring of the device name, e.g. a model number
+    NameContains(String),
+}
+
+impl DeviceMatcher {
+    /// Whether a device matches
+    pub fn matches(&self, device: &ScannerDevice) -> bool {
+        match self {
+            DeviceMatcher::Id(id) => device.id == *id,
+            DeviceMatcher::Name(name) => device.name.eq_ignore_ascii_case(name),
+            DeviceMatcher::NameContains(part) => device.name.to_lowercase().contains(&part.to_lowercase()),
+        }
+    }
+}
+
+/// Devices found by `ScanClient::get_devices_concurrent`
+#[derive(Debug, Clone, Default)]
+pub struct DeviceEnumeration {
+    /// Devices from every driver that responded, with duplicates removed
+    pub devices: Vec<ScannerDevice>,
+    /// Drivers whose enumeration failed, with the reason
+    pub errors: Vec<(Driver, String)>,
+}
+
+/// Outcome of `ScanClient::scan_with_driver_fallback`
+#[derive(Debug)]
+pub struct FallbackScanResult {
+    /// Driver that completed the scan
+    pub driver: Driver,
+    /// Device as seen through that driver
+    pub device: ScannerDevice,
+    /// Scanned pages
+    pub result: ScanResult,
+}
+
+/// Change in the set of connected devices reported by `ScanClient::watch_devices`
+#[derive(Debug, Clone)]
+pub enum DeviceEvent {
+    /// A device appeared (every device present at the first poll is reported as added)
+    Added(ScannerDevice),
+    /// A previously reported device disappeared
+    Removed(ScannerDevice),
+    /// Enumeration failed; the watcher keeps polling
+    Error(String),
+}
+
+/// Handle to a background device watcher
+///
+/// Derefs to the event receiver. Dropping the handle stops the watcher after its
+/// current poll.
+pub struct DeviceWatcher {
+    events: Receiver<DeviceEvent>,
+    stop: Arc<AtomicBool>,
+}
+
+impl std::ops::Deref for DeviceWatcher {
+    type Target = Receiver<DeviceEvent>;
+    
+    fn deref(&self) -> &Self::Target {
+        &self.events
+    }
+}
+
+impl Drop for DeviceWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A single page delivered while a scan is in progress
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScannedPage {
+    /// Zero-based position of the page in the scan
+    #[serde(rename = "Index")]
+    pub index: usize,
+    /// Path of the saved page image
+    #[serde(rename = "Path")]
+    pub path: String,
+    /// Resolution the page was actually scanned at, or the requested DPI if the driver didn't say
+    #[serde(rename = "Dpi", default)]
+    pub dpi: u32,
+}
+
+/// Move a file to `intended`, or next to it, as `policy` says; a skipped file is deleted
+pub(crate) fn place_file(from: &Path, intended: PathBuf, policy: OverwritePolicy) -> Result<SavedFile> {
+    let (target, action) = policy.resolve(intended.clone());
+    match target {
+        Some(target) => {
+            move_file(from, &target)?;
+            Ok(SavedFile { path: target.to_string_lossy().to_string(), action })
+        }
+        None => {
+            let _ = fs::remove_file(from);
+            Ok(SavedFile { path: intended.to_string_lossy().to_string(), action })
+        }
+    }
+}
+
+/// Merge drivers' device lists, keeping each scanner's first entry
+///
+/// Entries with the same `key` become alternates of the first; exact repeats
+/// of a driver and ID are dropped. Devices without a key are never merged.
+fn merge_devices(devices: Vec<ScannerDevice>) -> Vec<ScannerDevice> {
+    let mut seen = HashSet::new();
+    let mut merged: Vec<ScannerDevice> = Vec::new();
+    for device in devices {
+        if !seen.insert((device.driver.to_lowercase(), device.id.clone())) {
+            continue;
+        }
+        let first = device.key.as_ref()
+            .and_then(|key| merged.iter_mut().find(|first| first.key.as_ref() == Some(key)));
+        match first {
+            Some(first) => first.alternates.push(device),
+            None => merged.push(device),
+        }
+    }
+    merged
+}
+
+/// Run a scan command with a fresh temp directory, also returning how long it took
+///
+/// The directory is removed if `helper::kill_all` interrupts the scan.
+fn timed_scan(cmd: &mut Command) -> Result<(std::process::Output, Duration)> {
+    let started = Instant::now();
+    let temp_dir = helper::scan_temp_dir();
+    cmd.args(["--temp-dir", temp_dir.to_string_lossy().as_ref()]);
+    let output = helper::run_in(cmd, Some(temp_dir))?;
+    Ok((output, started.elapsed()))
+}
+
+/// Directory under the system temp directory holding device lockfiles
+const DEVICE_LOCK_DIR: &str = "naps2-device-locks";
+
+/// Scale `bounds`, found on a preview at `PREVIEW_DPI`, to a page scanned at `dpi`
+///
+/// The result is kept within the page, which can be a pixel or two smaller
+/// than the scaled preview.
+fn scale_preview_bounds(bounds: &Rect, dpi: u32, page_width: u32, page_height: u32) -> Rect {
+    let scale = dpi as f64 / PREVIEW_DPI as f64;
+    let x = ((bounds.x as f64 * scale).floor() as u32).min(page_width.saturating_sub(1));
+    let y = ((bounds.y as f64 * scale).floor() as u32).min(page_height.saturating_sub(1));
+    let width = ((bounds.width as f64 * scale).ceil() as u32).min(page_width - x).max(1);
+    let height = ((bounds.height as f64 * scale).ceil() as u32).min(page_height - y).max(1);
+    Rect::new(x, y, width, height)
+}
+
+/// A scan whose pages and temp directory are deleted on drop unless it's kept
+struct DiscardedScan(Option<ScanResult>);
+
+impl DiscardedScan {
+    fn result(&self) -> &ScanResult {
+        self.0.as_ref().expect("scan is held until kept")
+    }
+    
+    /// Hand the scan back to the caller instead of deleting it
+    fn keep(mut self) -> ScanResult {
+        self.0.take().expect("scan is held until kept")
+    }
+}
+
+impl Drop for DiscardedScan {
+    fn drop(&mut self) {
+        if let Some(result) = &self.0 {
+            for path in &result.image_paths {
+                let _ = fs::remove_file(path);
+            }
+            if !result.temp_directory.is_empty() {
+                let _ = fs::remove_dir_all(&result.temp_directory);
+            }
+        }
+    }
+}
+
+/// Move a file, copying and deleting when a rename can't cross filesystems
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to).with_context(|| format!("Failed to move {:?} to {:?}", from, to))?;
+        fs::remove_file(from).with_context(|| format!("Failed to remove {:?}", from))?;
+    }
+    Ok(())
+}
+
+/// Enforces a pause between scans on the same device
+///
+/// Each device has its own slot, so scans to one device queue behind each other
+/// while different devices proceed independently.
+#[derive(Debug)]
+struct ScanThrottle {
+    interval: Duration,
+    devices: Mutex<HashMap<String, Arc<ThrottleSlot>>>,
+}
+
+/// One device's turn in the throttle
+#[derive(Debug, Default)]
+struct ThrottleSlot {
+    state: Mutex<SlotState>,
+    freed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct SlotState {
+    busy: bool,
+    last_finished: Option<Instant>,
+}
+
+impl ScanThrottle {
+    /// Wait until the device is free and `interval` has passed since its last scan finished
+    fn acquire(&self, device_id: &str) -> ThrottlePermit {
+        let slot = self.devices.lock().unwrap_or_else(|e| e.into_inner())
+            .entry(device_id.to_string())
+            .or_default()
+            .clone();
+        let mut state = slot.state.lock().unwrap_or_else(|e| e.into_inner());
+        while state.busy {
+            state = slot.freed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        state.busy = true;
+        let wait = state.last_finished.map_or(Duration::ZERO, |finished| self.interval.saturating_sub(finished.elapsed()));
+        drop(state);
+        
+        thread::sleep(wait);
+        ThrottlePermit { slot, ran: true }
+    }
+}
+
+/// A device's turn in the throttle; dropping it marks the scan finished
+#[derive(Debug)]
+struct ThrottlePermit {
+    slot: Arc<ThrottleSlot>,
+    /// Whether the scan went ahead, so the next one has to wait out the interval
+    ran: bool,
+}
+
+impl ThrottlePermit {
+    /// Give up the turn without scanning, so the next caller isn't delayed
+    fn release_unused(mut self) {
+        self.ran = false;
+    }
+}
+
+impl Drop for ThrottlePermit {
+    fn drop(&mut self) {
+        let mut state = self.slot.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.busy = false;
+        if self.ran {
+            state.last_finished = Some(Instant::now());
+        }
+        self.slot.freed.notify_one();
+    }
+}
+
+/// A device's throttle permit and lock, whichever are configured, held until dropped
+///
+/// The lock is released first, so the throttle interval only starts once
+/// other processes can have the device too.
+#[derive(Debug)]
+struct DeviceReservation {
+    _lock: Option<DeviceLockGuard>,
+    _permit: Option<ThrottlePermit>,
 }
 
 /// Client for scanning operations
 pub struct ScanClient {
     helper_path: PathBuf,
+    working_dir: Option<PathBuf>,
+    throttle: Option<Arc<ScanThrottle>>,
+    device_lock: Option<DeviceLock>,
 }
 
 impl ScanClient {
     /// Create a new scan client with the path to the helper application
     pub fn new(helper_path: PathBuf) -> Self {
-        Self { helper_path }
+        Self { helper_path, working_dir: None, throttle: None, device_lock: None }
+    }
+    
+    /// Wait at least `interval` between the end of one scan and the start of the next on the same device
+    ///
+    /// Meant for shared network scanners that fail when jobs arrive back to
+    /// back. Further scans to a busy device block until it is their turn;
+    /// scans to other devices are unaffected. Applies to `scan`,
+    /// `scan_to_images`, `resume_or_scan` and the methods built on them, and
+    /// to a `scan_stream` for as long as the stream is alive.
+    pub fn with_throttle(mut self, interval: Duration) -> Self {
+        self.throttle = Some(Arc::new(ScanThrottle {
+            interval,
+            devices: Mutex::new(HashMap::new()),
+        }));
+        self
+    }
+    
+    /// Reserve the device across processes for the length of each scan
+    ///
+    /// Opt-in protection for scanners shared by several programs on one
+    /// machine: a scan first takes a lockfile for its device in the system temp
+    /// directory, waiting up to `timeout` for another process to finish before
+    /// failing with `Naps2Error::DeviceBusy`. Only processes that also use the
+    /// lock are kept out. See `with_device_lock_in` to share a different directory.
+    pub fn with_device_lock(self, timeout: Duration) -> Self {
+        self.with_device_lock_in(std::env::temp_dir().join(DEVICE_LOCK_DIR), timeout)
+    }
+    
+    /// Like `with_device_lock`, keeping the lockfiles in `dir`
+    pub fn with_device_lock_in<P: Into<PathBuf>>(mut self, dir: P, timeout: Duration) -> Self {
+        self.device_lock = Some(DeviceLock { dir: dir.into(), timeout });
+        self
+    }
+    
+    /// Wait for the throttle and device lock, if configured, and hold them until dropped
+    fn reserve(&self, device_id: &str) -> Result<DeviceReservation> {
+        let permit = self.throttle.as_ref().map(|throttle| throttle.acquire(device_id));
+        let lock = match self.device_lock.as_ref().map(|lock| lock.acquire(device_id)).transpose() {
+            Ok(lock) => lock,
+            Err(e) => {
+                // The scan never started, so it doesn't count toward the interval
+                if let Some(permit) = permit {
+                    permit.release_unused();
+                }
+                return Err(e);
+            }
+        };
+        Ok(DeviceReservation { _lock: lock, _permit: permit })
+    }
+    
+    /// Run a scan through the throttle and device lock, if configured
+    fn throttled<T>(&self, device_id: &str, scan: impl FnOnce() -> Result<T>) -> Result<T> {
+        let _reservation = self.reserve(device_id)?;
+        scan()
+    }
+    
+    /// Resolve relative paths against `working_dir` and run the helper from there
+    pub fn with_working_dir<P: Into<PathBuf>>(mut self, working_dir: P) -> Self {
+        self.working_dir = Some(working_dir.into());
+        self
+    }
+    
+    /// Start building a helper command
+    fn command(&self) -> Command {
+        helper::command(&self.helper_path, self.working_dir.as_deref())
+    }
+    
+    /// Resolve a relative path against `working_dir`, as the helper would
+    fn resolve(&self, path: &Path) -> PathBuf {
+        helper::resolve(self.working_dir.as_deref(), path)
     }
     
     /// Get a list of available scanning devices with a specific driver
     pub fn get_devices_with_driver(&self, driver: Option<Driver>) -> Result<Vec<ScannerDevice>> {
-        let mut cmd = Command::new(&self.helper_path);
+        let mut cmd = self.command();
         cmd.args(["scan", "list-devices"]);
         
         // Add driver argument if specified
@@ -97,19 +914,11 @@ impl ScanClient {
         }
         
         // Execute the helper application
-        let output = cmd.output()
-            .with_context(|| format!("Failed to execute helper at {:?}", self.helper_path))?;
-            
-        // Check if the command was successful
-        if !output.status.success() {
-            let error_message = String::from_utf8_lossy(&output.stderr).to_string();
-            return Err(Naps2Error::HelperExecutionError(error_message).into());
-        }
+        let output = helper::run(&mut cmd)?;
         
         // Parse the JSON output
         let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let devices: Vec<ScannerDevice> = serde_json::from_str(&stdout)
-            .map_err(|e| Naps2Error::HelperOutputError(format!("JSON parse error: {}", e)))?;
+        let devices: Vec<ScannerDevice> = helper::parse_json(&stdout)?;
             
         Ok(devices)
     }
@@ -119,10 +928,992 @@ impl ScanClient {
         self.get_devices_with_driver(None)
     }
     
+    /// Enumerate devices with several drivers at once, one helper per driver
+    ///
+    /// A scanner visible through more than one driver (e.g. a network scanner
+    /// found by both eSCL and SANE) is listed once, under the first driver in
+    /// `drivers` that reported it, with the other drivers' entries in
+    /// `ScannerDevice::alternates`. Entries are matched by `ScannerDevice::key`,
+    /// never by name, so two scanners of the same model stay separate; devices
+    /// the helper gave no key are listed as reported. A failing driver is
+    /// recorded in `errors` and doesn't fail the call.
+    pub fn get_devices_concurrent(&self, drivers: &[Driver]) -> DeviceEnumeration {
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = drivers.iter()
+                .map(|&driver| scope.spawn(move || self.get_devices_with_driver(Some(driver))))
+                .collect();
+            handles.into_iter().map(|handle| handle.join()).collect()
+        });
+        
+        let mut enumeration = DeviceEnumeration::default();
+        for (&driver, result) in drivers.iter().zip(results) {
+            match result {
+                Ok(Ok(devices)) => enumeration.devices.extend(devices),
+                Ok(Err(e)) => enumeration.errors.push((driver, e.to_string())),
+                Err(_) => enumeration.errors.push((driver, "device enumeration panicked".to_string())),
+            }
+        }
+        
+        enumeration.devices = merge_devices(enumeration.devices);
+        enumeration
+    }
+    
+    /// Get the only connected device
+    ///
+    /// Returns `Naps2Error::NoDevices` if none are found and
+    /// `Naps2Error::MultipleDevices` (listing them) if there is more than one.
+    pub fn get_single_device(&self, driver: Option<Driver>) -> Result<ScannerDevice> {
+        let mut devices = self.get_devices_with_driver(driver)?;
+        
+        match devices.len() {
+            0 => Err(Naps2Error::NoDevices.into()),
+            1 => Ok(devices.remove(0)),
+            _ => Err(Naps2Error::MultipleDevices(devices).into()),
+        }
+    }
+    
+    /// Serialize a device's identity so the app can store it and reconnect later
+    ///
+    /// Nothing is persisted by the crate; store the returned string wherever suits
+    /// the app and pass it to `reconnect_last` on the next launch.
+    pub fn remember_device(device: &ScannerDevice) -> Result<String> {
+        serde_json::to_string(device)
+            .map_err(|e| Naps2Error::HelperOutputError(format!("JSON serialize error: {}", e)).into())
+    }
+    
+    /// Resolve a device saved with `remember_device` to a currently connected device
+    ///
+    /// Only the remembered device's driver is enumerated, which is much faster than
+    /// searching every driver. The device is matched by ID, falling back to its name
+    /// since some drivers assign new IDs to network devices between sessions.
+    pub fn reconnect_last(&self, remembered: &str) -> Result<ScannerDevice> {
+        let saved: ScannerDevice = serde_json::from_str(remembered)
+            .map_err(|e| Naps2Error::HelperOutputError(format!("Invalid remembered device: {}", e)))?;
+        
+        let mut devices = self.get_devices_with_driver(Driver::from_name(&saved.driver))?;
+        
+        let by_id = devices.iter().position(|d| d.id == saved.id);
+        let index = by_id.or_else(|| devices.iter().position(|d| d.name == saved.name));
+        
+        match index {
+            Some(i) => Ok(devices.swap_remove(i)),
+            None => Err(Naps2Error::DeviceNotFoundError(
+                format!("{} ({}, {})", saved.name, saved.id, saved.driver)).into()),
+        }
+    }
+    
+    /// Watch for devices being connected or disconnected
+    ///
+    /// A background thread enumerates `driver` every `interval` and emits events
+    /// for devices that were added or removed since the previous poll, matched by
+    /// device ID. Each poll costs a full enumeration, so intervals below a few
+    /// seconds are rarely worthwhile.
+    pub fn watch_devices(&self, driver: Option<Driver>, interval: Duration) -> DeviceWatcher {
+        let (sender, events) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let client = ScanClient {
+            helper_path: self.helper_path.clone(),
+            working_dir: self.working_dir.clone(),
+            throttle: None,
+            device_lock: None,
+        };
+        let thread_stop = stop.clone();
+        
+        thread::spawn(move || {
+            let mut known: Vec<ScannerDevice> = Vec::new();
+            
+            while !thread_stop.load(Ordering::Relaxed) {
+                let mut changes = Vec::new();
+                
+                match client.get_devices_with_driver(driver) {
+                    Ok(current) => {
+                        for device in &current {
+                            if !known.iter().any(|d| d.id == device.id) {
+                                changes.push(DeviceEvent::Added(device.clone()));
+                            }
+                        }
+                        for device in &known {
+                            if !current.iter().any(|d| d.id == device.id) {
+                                changes.push(DeviceEvent::Removed(device.clone()));
+                            }
+                        }
+                        known = current;
+                    }
+                    Err(e) => changes.push(DeviceEvent::Error(e.to_string())),
+                }
+                
+                // Stop once nobody is listening
+                if changes.into_iter().any(|event| sender.send(event).is_err()) {
+                    break;
+                }
+                
+                thread::sleep(interval);
+            }
+        });
+        
+        DeviceWatcher { events, stop }
+    }
+    
+    /// Query the current status of a known device
+    ///
+    /// Unlike enumeration this only probes the given device, so a call
+    /// typically completes in well under a second (local USB devices in
+    /// ~100-300ms, network eSCL devices up to ~2s). Polling every 1-2
+    /// seconds is enough for a live status indicator.
+    pub fn device_status(&self, device_id: &str, driver: Option<Driver>) -> Result<DeviceStatus> {
+        let mut cmd = self.command();
+        cmd.args(["scan", "status", device_id]);
+        
+        // Add driver argument if specified
+        if let Some(drv) = driver {
+            cmd.arg(drv.to_string());
+        }
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let response: DeviceStatusResponse = helper::parse_json(&stdout)?;
+            
+        Ok(response.status)
+    }
+    
+    /// Read the manufacturer, model, serial number and firmware version a device reports
+    ///
+    /// Useful for asset tracking and support. What's available depends on the
+    /// driver: eSCL reports all four from the scanner's capabilities, TWAIN and
+    /// WIA report what the vendor's driver fills in, and SANE usually only has
+    /// the vendor and model.
+    pub fn get_device_info(&self, device_id: &str, driver: Option<Driver>) -> Result<DeviceInfo> {
+        let mut cmd = self.command();
+        cmd.args(["scan", "info", device_id]);
+        
+        // Add driver argument if specified
+        if let Some(drv) = driver {
+            cmd.arg(drv.to_string());
+        }
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let info: DeviceInfo = helper::parse_json(&stdout)?;
+            
+        Ok(info)
+    }
+    
+    /// Check whether paper is loaded in a device's document feeder
+    ///
+    /// Returns `None` when the answer is unknown. The sensor is read through
+    /// TWAIN (`CAP_FEEDERLOADED`), WIA (document handling status) and eSCL
+    /// (ADF state); with SANE it is only available from backends that expose a
+    /// paper sensor option. Other drivers, and devices without a feeder sensor,
+    /// always report `None`.
+    pub fn feeder_has_paper(&self, device_id: &str, driver: Option<Driver>) -> Result<Option<bool>> {
+        let mut cmd = self.command();
+        cmd.args(["scan", "feeder", device_id]);
+        
+        // Add driver argument if specified
+        if let Some(drv) = driver {
+            cmd.arg(drv.to_string());
+        }
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let response: FeederResponse = helper::parse_json(&stdout)?;
+            
+        Ok(response.has_paper)
+    }
+    
+    /// List the paper sources a device offers
+    ///
+    /// An empty list means the driver didn't say, in which case any source may
+    /// be requested and the driver decides what happens.
+    pub fn supported_sources(&self, device_id: &str, driver: Option<Driver>) -> Result<Vec<PaperSource>> {
+        let mut cmd = self.command();
+        cmd.args(["scan", "sources", device_id]);
+        
+        // Add driver argument if specified
+        if let Some(drv) = driver {
+            cmd.arg(drv.to_string());
+        }
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let response: SourcesResponse = helper::parse_json(&stdout)?;
+            
+        Ok(response.sources.iter().filter_map(|s| PaperSource::from_name(s)).collect())
+    }
+    
+    /// Check a requested paper source against the device, falling back to the flatbed if allowed
+    ///
+    /// Returns the source to scan from and a warning if it differs from the request.
+    /// `PaperSource::Auto` is resolved first, picking the flatbed if the feeder
+    /// can't be read. If the sources can't be queried, as with helpers that lack
+    /// `scan sources`, the request is passed through unchecked.
+    fn check_paper_source(&self, device_id: &str, driver: Option<Driver>, requested: PaperSource,
+                          fallback_to_flatbed: bool) -> Result<(PaperSource, Option<String>)> {
+        let requested = match requested {
+            PaperSource::Auto => match self.feeder_has_paper(device_id, driver).ok().flatten() {
+                Some(true) => PaperSource::Feeder,
+                _ => PaperSource::Flatbed,
+            },
+            source => source,
+        };
+        
+        // Helpers without the query, or drivers that fail it, simply don't report sources
+        let supported = self.supported_sources(device_id, driver).unwrap_or_default();
+        if supported.is_empty() || supported.contains(&requested) {
+            return Ok((requested, None));
+        }
+        
+        if fallback_to_flatbed && requested != PaperSource::Flatbed && supported.contains(&PaperSource::Flatbed) {
+            let warning = format!("{} is not supported by this device; scanned from the flatbed instead",
+                                  requested.to_string());
+            return Ok((PaperSource::Flatbed, Some(warning)));
+        }
+        
+        Err(Naps2Error::UnsupportedPaperSource { requested, supported }.into())
+    }
+    
+    /// Copy of `options` with the paper source checked by `check_paper_source`
+    fn with_checked_source(&self, device_id: &str, options: &ScanOptions) -> Result<(ScanOptions, Option<String>)> {
+        let mut options = options.clone();
+        let mut warning = None;
+        if let Some(requested) = options.paper_source {
+            let (source, fallback) = self.check_paper_source(device_id, options.driver, requested,
+                                                             options.fallback_to_flatbed)?;
+            options.paper_source = Some(source);
+            warning = fallback;
+        }
+        Ok((options, warning))
+    }
+    
+    /// Interleave the front and back passes of a manual duplex scan into page order
+    ///
+    /// Set `reverse_back` when the stack was flipped over for the second pass, so
+    /// the backs were scanned last page first. The combined result keeps the front
+    /// pass's temp directory; the back pass's images stay where they were scanned.
+    /// Passes of different lengths fail with `Naps2Error::DuplexMismatch` rather
+    /// than producing a misordered document.
+    pub fn combine_passes(front: ScanResult, back: ScanResult, reverse_back: bool) -> Result<ScanResult> {
+        if front.image_paths.len() != back.image_paths.len() {
+            return Err(Naps2Error::DuplexMismatch {
+                fronts: front.image_paths.len(),
+                backs: back.image_paths.len(),
+            }.into());
+        }
+        
+        let mut backs = back.image_paths;
+        let mut back_dpis = back.page_dpis;
+        if reverse_back {
+            backs.reverse();
+            back_dpis.reverse();
+        }
+        
+        let page_dpis = if front.page_dpis.len() == backs.len() && back_dpis.len() == backs.len() {
+            front.page_dpis.into_iter().zip(back_dpis).flat_map(|(f, b)| [f, b]).collect()
+        } else {
+            Vec::new()
+        };
+        let image_paths = front.image_paths.into_iter()
+            .zip(backs)
+            .flat_map(|(f, b)| [f, b])
+            .collect();
+        let mut warnings = front.warnings;
+        warnings.extend(back.warnings);
+        
+        Ok(ScanResult {
+            image_paths,
+            temp_directory: front.temp_directory,
+            warnings,
+            paper_source: front.paper_source,
+            page_dpis,
+            timing: None,
+        })
+    }
+    
+    /// Interleave duplex passes, padding the shorter pass with blank pages
+    ///
+    /// Use this when a count mismatch is expected, e.g. the last sheet has no
+    /// back. Blank pages are appended at the end of the shorter pass (after any
+    /// reversal), matching the size of its neighbouring page, and are written to
+    /// the front pass's temp directory.
+    pub fn combine_passes_padded(&self, mut front: ScanResult, mut back: ScanResult,
+                                 reverse_back: bool) -> Result<ScanResult> {
+        if reverse_back {
+            back.reverse_pages();
+        }
+        
+        let target = front.image_paths.len().max(back.image_paths.len());
+        let temp_directory = PathBuf::from(&front.temp_directory);
+        
+        // An empty pass takes its page size from the other one
+        let fallback = front.image_paths.first().or(back.image_paths.first()).cloned()
+            .ok_or_else(|| Naps2Error::NoPagesScanned("Both duplex passes are empty".to_string()))?;
+        let fallback_dpi = front.page_dpis.first().or(back.page_dpis.first()).copied();
+        
+        for pass in [&mut front, &mut back] {
+            while pass.image_paths.len() < target {
+                let reference = pass.image_paths.last().cloned().unwrap_or_else(|| fallback.clone());
+                let blank = self.blank_page(&reference, &temp_directory)?;
+                
+                // Blank pages match their reference's resolution
+                if let Some(dpi) = pass.page_dpis.last().copied().or(fallback_dpi) {
+                    if pass.page_dpis.len() == pass.image_paths.len() {
+                        pass.page_dpis.push(dpi);
+                    }
+                }
+                pass.image_paths.push(blank);
+            }
+        }
+        
+        Self::combine_passes(front, back, false)
+    }
+    
+    /// Have the helper write a white page with the same size and format as `reference`
+    fn blank_page(&self, reference: &str, directory: &Path) -> Result<String> {
+        let extension = Path::new(reference).extension()
+            .map(|e| e.to_string_lossy().to_string())
+            .unwrap_or_else(|| "png".to_string());
+        let output_path = unique_path(directory, "blank", &extension);
+        
+        let mut cmd = self.command();
+        cmd.args(["image", "blank", reference, output_path.to_string_lossy().as_ref()]);
+        
+        // Execute the helper application
+        let output = helper::run(&mut cmd)?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let path: String = helper::parse_json(&stdout)?;
+            
+        Ok(path)
+    }
+    
+    /// Build the helper command for a scan with the given options
+    fn scan_command(&self, device_id: &str, options: &ScanOptions) -> Command {
+        let mut cmd = self.command();
+        cmd.args(["scan", "to-images", device_id]);
+        
+        // Positional arguments are always passed so later ones stay aligned
+        cmd.arg(options.driver.unwrap_or(Driver::Default).to_string());
+        cmd.arg(options.dpi.to_string());
+        cmd.arg(options.paper_source.map(|s| s.to_string()).unwrap_or(""));
+        
+        // Add optional settings
+        if let Some(page_size) = options.page_size {
+            cmd.args(["--page-size", &page_size.to_arg()]);
+        }
+        cmd.args(["--color-mode", options.color_mode.to_string()]);
+        if let Some(threshold) = options.bw_threshold {
+            cmd.args(["--bw-threshold", &threshold.to_string()]);
+        }
+        if let Some(gamma) = options.gamma {
+            cmd.args(["--gamma", &gamma.to_string()]);
+        }
+        if options.brightness != 0 {
+            cmd.args(["--brightness", &options.brightness.to_string()]);
+        }
+        if options.contrast != 0 {
+            cmd.args(["--contrast", &options.contrast.to_string()]);
+        }
+        if options.show_native_ui {
+            cmd.arg("--native-ui");
+        }
+        if let Some(escl) = &options.escl {
+            if escl.https {
+                cmd.arg("--escl-https");
+            }
+            if escl.accept_invalid_certificates {
+                cmd.arg("--escl-insecure");
+            }
+            if let Some(cert) = &escl.client_certificate {
+                cmd.args(["--escl-client-cert", cert.to_string_lossy().as_ref()]);
+            }
+            if let Some(key) = &escl.client_key {
+                cmd.args(["--escl-client-key", key.to_string_lossy().as_ref()]);
+            }
+        }
+        
+        cmd
+    }
+    
+    /// Scan using the specified device and options, saving to images
+    ///
+    /// The options are validated, including the `ScanOptions::max_megapixels`
+    /// guard, before the helper is started.
+    pub fn scan(&self, device_id: &str, options: &ScanOptions) -> Result<ScanResult> {
+        options.validate()?;
+        if options.auto_detect {
+            return Ok(self.scan_auto_detect(device_id, options)?.result);
+        }
+        
+        let (options, warning) = self.with_checked_source(device_id, options)?;
+        let mut cmd = self.scan_command(device_id, &options);
+        
+        // Execute the helper application
+        let (output, elapsed) = self.throttled(device_id, || timed_scan(&mut cmd))?;
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let mut result: ScanResult = helper::parse_json(&stdout)?;
+            
+        if result.image_paths.is_empty() && !options.allow_empty {
+            return Err(Naps2Error::NoPagesScanned(format!("{} returned no pages", device_id)).into());
+        }
+        result.warnings.extend(warning);
+        result.paper_source = options.paper_source;
+        result.fill_page_dpis(options.dpi);
+        result.timing = Some(ScanTiming::from_output(&stdout, elapsed)?);
+        if options.reverse_order {
+            result.reverse_pages();
+        }
+        
+        Ok(result)
+    }
+    
+    /// Preview the page, classify it and scan it with settings to suit
+    ///
+    /// A 75 DPI color preview is analyzed with `ImageClient::analyze` and
+    /// `ImageClient::content_bounds`:
+    ///
+    /// - content under 100mm in both directions is a business card, scanned in
+    ///   color at 600 DPI and cropped to where the card lay on the preview
+    /// - almost entirely black-and-white, uncolored content is text, scanned in
+    ///   grayscale at 300 DPI
+    /// - anything else is a photo, scanned in color at 600 DPI
+    ///
+    /// Other options, such as brightness, are kept. Only the flatbed is supported.
+    pub fn scan_auto_detect(&self, device_id: &str, options: &ScanOptions) -> Result<AutoDetectedScan> {
+        options.validate()?;
+        
+        let base = ScanOptions { auto_detect: false, ..options.clone() };
+        let preview_options = ScanOptions {
+            dpi: PREVIEW_DPI,
+            color_mode: ColorMode::Color,
+            page_size: None,
+            bw_threshold: None,
+            gamma: None,
+            reverse_order: false,
+            ..base.clone()
+        };
+        
+        // The preview is only needed for analysis, so it's discarded however this ends
+        let preview = DiscardedScan(Some(self.scan(device_id, &preview_options)?));
+        let page = preview.result().image_paths.first().ok_or_else(|| Naps2Error::NoPagesScanned(
+            "Preview scan returned no pages".to_string()))?;
+        
+        let mut images = ImageClient::new(self.helper_path.clone());
+        if let Some(dir) = &self.working_dir {
+            images = images.with_working_dir(dir.clone());
+        }
+        let content = images.analyze(page)?;
+        let bounds = images.content_bounds(page)?;
+        drop(preview);
+        
+        let text_rules = AutoFormat::default();
+        let to_mm = |pixels: u32| pixels as f64 / PREVIEW_DPI as f64 * 25.4;
+        let (width_mm, height_mm) = (to_mm(bounds.width), to_mm(bounds.height));
+        
+        let (document_type, options) = if width_mm < CARD_MAX_MM && height_mm < CARD_MAX_MM {
+            // Scan the same area as the preview so the card's position carries over
+            (DocumentType::BusinessCard, ScanOptions {
+                dpi: 600,
+                color_mode: ColorMode::Color,
+                page_size: None,
+                bw_threshold: None,
+                gamma: None,
+                ..base
+            })
+        } else if text_rules.choose(&content) == text_rules.text_format {
+            (DocumentType::Text, ScanOptions {
+                dpi: 300,
+                color_mode: ColorMode::Grayscale,
+                bw_threshold: None,
+                gamma: None,
+                ..base
+            })
+        } else {
+            (DocumentType::Photo, ScanOptions {
+                dpi: 600,
+                color_mode: ColorMode::Color,
+                bw_threshold: None,
+                gamma: None,
+                ..base
+            })
+        };
+        
+        let result = self.scan(device_id, &options)?;
+        if document_type != DocumentType::BusinessCard {
+            return Ok(AutoDetectedScan { document_type, options, result });
+        }
+        
+        // If a crop fails, every page of the scan is discarded along with the error
+        let mut scan = DiscardedScan(Some(result));
+        for index in 0..scan.result().image_paths.len() {
+            let path = scan.result().image_paths[index].clone();
+            let (width, height) = images.dimensions(&path)?;
+            let cropped = images.crop(&path, &scale_preview_bounds(&bounds, options.dpi, width, height))?;
+            let _ = fs::remove_file(&path);
+            scan.0.as_mut().expect("scan is held until kept").image_paths[index] = cropped;
+        }
+        Ok(AutoDetectedScan { document_type, options, result: scan.keep() })
+    }
+    
+    /// Scan with a device returned by enumeration, using the driver it was found with
+    ///
+    /// `options.driver` is replaced by the device's own driver, so the two can't
+    /// be mismatched.
+    pub fn scan_device(&self, device: &ScannerDevice, options: &ScanOptions) -> Result<ScanResult> {
+        let driver = Driver::from_name(&device.driver).ok_or_else(|| Naps2Error::InvalidOptionsError(
+            format!("Unknown driver {:?} for device {}", device.driver, device.name)))?;
+        
+        self.scan(&device.id, &ScanOptions { driver: Some(driver), ..options.clone() })
+    }
+    
+    /// Scan into a checkpoint directory, continuing after any pages already saved there
+    ///
+    /// Pages are written to `checkpoint_dir` as `page<N>.<ext>` as they are scanned.
+    /// If a previous run was interrupted, reload the remaining sheets and call this
+    /// again with the same directory: numbering continues after the last saved page
+    /// and the returned result contains every page in order. If the scan fails
+    /// part-way, the pages saved so far are returned in
+    /// `Naps2Error::ScanInterruptedError` so they aren't lost.
+    pub fn resume_or_scan<P: AsRef<Path>>(&self, device_id: &str, options: &ScanOptions,
+                                          checkpoint_dir: P) -> Result<ScanResult> {
+        options.validate()?;
+        
+        let checkpoint_dir = &self.resolve(checkpoint_dir.as_ref());
+        fs::create_dir_all(checkpoint_dir)
+            .with_context(|| format!("Failed to create checkpoint directory {:?}", checkpoint_dir))?;
+        let existing = checkpoint_pages(checkpoint_dir)?;
+        
+        let (options, warning) = self.with_checked_source(device_id, options)?;
+        let mut cmd = self.scan_command(device_id, &options);
+        cmd.args(["--checkpoint-dir", checkpoint_dir.to_string_lossy().as_ref()]);
+        cmd.args(["--start-page", &(existing.len() + 1).to_string()]);
+        
+        // Execute the helper application
+        let output = self.throttled(device_id, || helper::capture(&mut cmd))?;
+            
+        // On failure, hand back whatever made it to disk
+        if !output.status.success() {
+            let reason = helper::exit_error(output.status.code(), &output.stderr).to_string();
+            let mut partial = ScanResult {
+                image_paths: checkpoint_pages(checkpoint_dir)?,
+                temp_directory: checkpoint_dir.to_string_lossy().to_string(),
+                warnings: Vec::new(),
+                paper_source: None,
+                page_dpis: Vec::new(),
+                timing: None,
+            };
+            if options.reverse_order {
+                partial.reverse_pages();
+            }
+            return Err(Naps2Error::ScanInterruptedError { reason, partial }.into());
+        }
+        
+        // Parse the JSON output
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let mut result: ScanResult = helper::parse_json(&stdout)?;
+        
+        // Earlier sessions are assumed to have used the same settings
+        let mut page_dpis = vec![options.dpi; existing.len()];
+        let mut image_paths = existing;
+        result.fill_page_dpis(options.dpi);
+        page_dpis.extend(result.page_dpis);
+        image_paths.extend(result.image_paths);
+        if image_paths.is_empty() && !options.allow_empty {
+            return Err(Naps2Error::NoPagesScanned(format!("{} returned no pages", device_id)).into());
+        }
+        
+        // Pages from every session are reversed together, as one batch
+        let mut result = ScanResult {
+            image_paths,
+            temp_directory: checkpoint_dir.to_string_lossy().to_string(),
+            warnings: warning.into_iter().collect(),
+            paper_source: options.paper_source,
+            page_dpis,
+            timing: None,
+        };
+        if options.reverse_order {
+            result.reverse_pages();
+        }
+        Ok(result)
+    }
+    
+    /// Scan as an async stream that yields each page as soon as it is saved
+    ///
+    /// Must be called from within a Tokio runtime. Dropping the stream, or
+    /// `helper::kill_all`, kills the helper process, cancelling the scan. If
+    /// the helper fails, the error is yielded as the final item after any pages
+    /// that were already delivered; so is `Naps2Error::NoPagesScanned` if it
+    /// produced none, unless `ScanOptions::allow_empty` is set.
+    ///
+    /// The throttle and device lock, if configured, are waited for before the
+    /// helper starts, blocking the caller, and held until the stream ends or
+    /// is dropped.
+    #[cfg(feature = "async")]
+    pub fn scan_stream(&self, device_id: &str, options: &ScanOptions)
+                       -> Result<impl futures_util::Stream<Item = Result<ScannedPage>>> {
+        use std::process::Stdio;
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+        
+        options.validate()?;
+        
+        let (options, _) = self.with_checked_source(device_id, options)?;
+        let (dpi, allow_empty, device) = (options.dpi, options.allow_empty, device_id.to_string());
+        let temp_dir = helper::scan_temp_dir();
+        let mut cmd = self.scan_command(device_id, &options);
+        cmd.arg("--stream")
+            .args(["--temp-dir", temp_dir.to_string_lossy().as_ref()])
+            .args(helper::extra_args())
+            .stdin(Stdio::null());
+        
+        let reservation = self.reserve(device_id)?;
+        let (child, stdout, stderr) = helper::TrackedChild::spawn(&mut cmd, Some(temp_dir))?;
+        let lines = BufReader::new(tokio::process::ChildStdout::from_std(stdout)
+            .context("Failed to read helper output")?).lines();
+        
+        // Drain stderr in the background so a chatty helper can't block on a full pipe
+        let mut stderr = tokio::process::ChildStderr::from_std(stderr).context("Failed to read helper output")?;
+        let stderr_task = tokio::spawn(async move {
+            let mut buffer = String::new();
+            let _ = stderr.read_to_string(&mut buffer).await;
+            buffer
+        });
+        
+        // The helper prints one JSON object per line as each page is saved. The
+        // reservation travels with the state, so it's released when that is dropped.
+        let state = Some((child, lines, stderr_task, reservation, 0usize));
+        Ok(futures_util::stream::unfold(state, move |state| {
+            let device = device.clone();
+            async move {
+                let (child, mut lines, stderr_task, reservation, count) = state?;
+                
+                match lines.next_line().await {
+                    Ok(Some(line)) => {
+                        let page = helper::parse_json::<ScannedPage>(&line).map(|mut page| {
+                            if page.dpi == 0 {
+                                page.dpi = dpi;
+                            }
+                            page
+                        });
+                        Some((page, Some((child, lines, stderr_task, reservation, count + 1))))
+                    }
+                    Ok(None) => {
+                        let status = tokio::task::spawn_blocking(move || child.wait()).await
+                            .unwrap_or_else(|_| Err(Naps2Error::HelperExecutionError("helper wait panicked".to_string()).into()));
+                        drop(reservation);
+                        let error_message = stderr_task.await.unwrap_or_default();
+                        match status {
+                            Ok(status) if status.success() && count == 0 && !allow_empty => Some((Err(
+                                Naps2Error::NoPagesScanned(format!("{} returned no pages", device)).into()), None)),
+                            Ok(status) if status.success() => None,
+                            Ok(status) => Some((Err(helper::exit_error(status.code(), error_message.as_bytes()).into()), None)),
+                            Err(e) => Some((Err(e), None)),
+                        }
+                    }
+                    Err(e) => Some((Err(e.into()), None)),
+                }
+            }
+        }))
+    }
+    
+    /// Scan, handing each page to `sink` as soon as it is saved, and return the number of pages
+    ///
+    /// The helper still saves each page to a temp file, which is read, passed
+    /// to the sink and deleted before the next page is taken, so a long batch
+    /// never accumulates on local disk. If the sink fails, the helper is killed
+    /// and the sink's error returned; pages already accepted stay with the sink.
+    /// `ScanOptions::reverse_order` is rejected, since pages are handed over
+    /// in the order they are scanned.
+    pub fn scan_to_sink(&self, device_id: &str, options: &ScanOptions, sink: &mut dyn PageSink) -> Result<usize> {
+        use std::io::{BufRead, BufReader};
+        use std::process::Stdio;
+        
+        options.validate()?;
+        if options.reverse_order {
+            return Err(Naps2Error::InvalidOptionsError(
+                "reverse_order can't be used with scan_to_sink, which hands over pages as they are scanned".to_string()).into());
+        }
+        
+        let (options, _) = self.with_checked_source(device_id, options)?;
+        let temp_dir = helper::scan_temp_dir();
+        let mut cmd = self.scan_command(device_id, &options);
+        cmd.arg("--stream")
+            .args(["--temp-dir", temp_dir.to_string_lossy().as_ref()])
+            .args(helper::extra_args())
+            .stdin(Stdio::null());
+        
+        let count = self.throttled(device_id, || {
+            let (child, stdout, stderr) = helper::TrackedChild::spawn(&mut cmd, Some(temp_dir.clone()))?;
+            
+            // Drain stderr on its own thread so a chatty helper can't block on a full pipe
+            let limit = helper::output_limit();
+            let stderr_reader = thread::spawn(move || helper::read_limited(stderr, limit));
+            
+            // The helper prints one JSON object per line as each page is saved
+            let mut count = 0;
+            let delivered = BufReader::new(stdout).lines().try_for_each(|line| -> Result<()> {
+                let line = line.context("Failed to read helper output")?;
+                let page: ScannedPage = helper::parse_json(&line)?;
+                let bytes = fs::read(&page.path).with_context(|| format!("Failed to read page {:?}", page.path))?;
+                let format = ImageFormat::from_magic_bytes(&bytes).ok_or_else(|| Naps2Error::ImageError(
+                    format!("Unrecognized image format: {}", page.path)))?;
+                sink.accept_page(page.index, &bytes, format)?;
+                
+                let path = Path::new(&page.path);
+                let _ = fs::remove_file(path);
+                if let Some(dir) = path.parent() {
+                    let _ = fs::remove_dir(dir);
+                }
+                count += 1;
+                Ok(())
+            });
+            if let Err(e) = delivered {
+                child.kill();
+                let _ = child.wait();
+                let _ = fs::remove_dir_all(&temp_dir);
+                return Err(e);
+            }
+            
+            let status = child.wait()?;
+            let (stderr, _) = stderr_reader.join()
+                .map_err(|_| Naps2Error::HelperExecutionError("stderr reader panicked".to_string()))?
+                .context("Failed to read helper output")?;
+            if !status.success() {
+                return Err(helper::exit_error(status.code(), &stderr).into());
+            }
+            Ok(count)
+        })?;
+        
+        if count == 0 && !options.allow_empty {
+            return Err(Naps2Error::NoPagesScanned(format!("{} returned no pages", device_id)).into());
+        }
+        Ok(count)
+    }
+    
+    /// Try each driver in order until one finds a matching device and scans successfully
+    ///
+    /// `options.driver` is ignored; each driver in `drivers` is used in turn. If every
+    /// driver fails, the error lists what went wrong with each one.
+    pub fn scan_with_driver_fallback(&self, matcher: &DeviceMatcher, drivers: &[Driver],
+                                     options: &ScanOptions) -> Result<FallbackScanResult> {
+        let mut failures = Vec::new();
+        
+        for &driver in drivers {
+            let devices = match self.get_devices_with_driver(Some(driver)) {
+                Ok(devices) => devices,
+                Err(e) => {
+                    failures.push(format!("{}: {}", driver.to_string(), e));
+                    continue;
+                }
+            };
+            
+            let Some(device) = devices.into_iter().find(|d| matcher.matches(d)) else {
+                failures.push(format!("{}: no matching device", driver.to_string()));
+                continue;
+            };
+            
+            let driver_options = ScanOptions { driver: Some(driver), ..options.clone() };
+            match self.scan(&device.id, &driver_options) {
+                Ok(result) => return Ok(FallbackScanResult { driver, device, result }),
+                Err(e) => failures.push(format!("{}: {}", driver.to_string(), e)),
+            }
+        }
+        
+        Err(Naps2Error::DeviceNotFoundError(
+            format!("{:?} could not be scanned with any driver ({})", matcher, failures.join("; "))).into())
+    }
+    
+    /// Scan and move the pages into a folder, named from a template
+    ///
+    /// `naming_template` is the file stem without extension, e.g. `scan-{date}-{page:03}`;
+    /// see `TemplateNaming` for the placeholders. Otherwise as `scan_to_folder_named`.
+    pub fn scan_to_folder<P: AsRef<Path>>(&self, device_id: &str, options: &ScanOptions,
+                                          folder: P, naming_template: &str) -> Result<Vec<String>> {
+        self.scan_to_folder_named(device_id, options, folder, &TemplateNaming(naming_template.to_string()))
+    }
+    
+    /// Scan and move the pages into a folder, named by a naming strategy
+    ///
+    /// The original extension is kept. Existing files are never overwritten: a
+    /// ` (1)`, ` (2)`... suffix is added instead. Returns the final paths in page order.
+    pub fn scan_to_folder_named<P: AsRef<Path>>(&self, device_id: &str, options: &ScanOptions,
+                                                folder: P, naming: &dyn OutputNaming) -> Result<Vec<String>> {
+        let saved = self.scan_to_folder_with_policy(device_id, options, folder, naming, OverwritePolicy::Rename)?;
+        Ok(saved.into_iter().map(|file| file.path).collect())
+    }
+    
+    /// Scan and move the pages into a folder, applying `policy` to names that already exist
+    ///
+    /// Returns each page's outcome in page order. Pages skipped under
+    /// `OverwritePolicy::Skip` are deleted, and their entry holds the existing file.
+    pub fn scan_to_folder_with_policy<P: AsRef<Path>>(&self, device_id: &str, options: &ScanOptions, folder: P,
+                                                      naming: &dyn OutputNaming, policy: OverwritePolicy) -> Result<Vec<SavedFile>> {
+        let folder = &self.resolve(folder.as_ref());
+        fs::create_dir_all(folder)
+            .with_context(|| format!("Failed to create folder {:?}", folder))?;
+        
+        let result = self.scan(device_id, options)?;
+        let timestamp = iso8601(SystemTime::now());
+        
+        let mut saved = Vec::with_capacity(result.image_paths.len());
+        for (i, source) in result.image_paths.iter().enumerate() {
+            let source = Path::new(source);
+            let extension = source.extension().and_then(|e| e.to_str()).unwrap_or("jpg");
+            let context = NamingContext { source: Some(source), timestamp: &timestamp };
+            let intended = folder.join(format!("{}.{}", naming.name(i, &context), extension));
+            saved.push(place_file(source, intended, policy)?);
+        }
+        
+        Ok(saved)
+    }
+    
+    /// Scan a single page straight to `output_path`
+    ///
+    /// The format is taken from the extension, e.g. `.png` or `.jpg`, and the
+    /// page is converted if the scanner produced something else. An existing
+    /// file at `output_path` is replaced. Fails without writing anything if the
+    /// device returns more than one page, as a feeder might.
+    pub fn scan_single_to_file<P: AsRef<Path>>(&self, device_id: &str, options: &ScanOptions,
+                                               output_path: P) -> Result<()> {
+        self.scan_single_to_file_with_policy(device_id, options, output_path, OverwritePolicy::Overwrite)?;
+        Ok(())
+    }
+    
+    /// Scan a single page to `output_path`, applying `policy` if it already exists
+    ///
+    /// Otherwise as `scan_single_to_file`.
+    pub fn scan_single_to_file_with_policy<P: AsRef<Path>>(&self, device_id: &str, options: &ScanOptions,
+                                                           output_path: P, policy: OverwritePolicy) -> Result<SavedFile> {
+        let output_path = &self.resolve(output_path.as_ref());
+        let format = output_path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(ImageFormat::from_extension)
+            .ok_or_else(|| Naps2Error::InvalidOptionsError(
+                format!("Can't tell the image format from the extension of {:?}", output_path)))?;
+        
+        let result = self.scan(device_id, options)?;
+        if result.image_paths.len() != 1 {
+            for path in &result.image_paths {
+                let _ = fs::remove_file(path);
+            }
+            return Err(Naps2Error::ScanningError(format!(
+                "Expected a single page but the device returned {}", result.image_paths.len())).into());
+        }
+        let page = Path::new(&result.image_paths[0]);
+        
+        let mut images = ImageClient::new(self.helper_path.clone());
+        if let Some(dir) = &self.working_dir {
+            images = images.with_working_dir(dir.clone());
+        }
+        let converted = PathBuf::from(images.convert(page, format)?);
+        
+        let saved = place_file(&converted, output_path.to_path_buf(), policy)?;
+        if converted != page {
+            let _ = fs::remove_file(page);
+        }
+        
+        Ok(saved)
+    }
+    
+    /// Scan into a folder laid out like a NAPS2 desktop recovery folder
+    ///
+    /// Pages are moved into `folder` as `page-0001.jpg`... and listed in an
+    /// `index.xml` the desktop app understands, so an interrupted batch can be
+    /// opened there by copying the folder into its recovery directory. The
+    /// returned result's temp directory is `folder`.
+    pub fn scan_to_recovery_folder<P: AsRef<Path>>(&self, device_id: &str, options: &ScanOptions,
+                                                   folder: P) -> Result<ScanResult> {
+        let folder = &self.resolve(folder.as_ref());
+        fs::create_dir_all(folder)
+            .with_context(|| format!("Failed to create folder {:?}", folder))?;
+        
+        let result = self.scan(device_id, options)?;
+        
+        let mut file_names = Vec::with_capacity(result.image_paths.len());
+        let mut image_paths = Vec::with_capacity(result.image_paths.len());
+        for (i, source) in result.image_paths.iter().enumerate() {
+            let source = Path::new(source);
+            let extension = source.extension().and_then(|e| e.to_str()).unwrap_or("jpg");
+            let target = unique_path(folder, &format!("page-{:04}", i + 1), extension);
+            
+            move_file(source, &target)?;
+            file_names.push(target.file_name().unwrap_or_default().to_string_lossy().to_string());
+            image_paths.push(target.to_string_lossy().to_string());
+        }
+        recovery::write_index(folder, &file_names, options.color_mode)?;
+        
+        // Every page has moved out, so the scan's own temp directory is now empty
+        if !result.temp_directory.is_empty() && Path::new(&result.temp_directory) != folder {
+            let _ = fs::remove_dir(&result.temp_directory);
+        }
+        
+        Ok(ScanResult {
+            image_paths,
+            temp_directory: folder.to_string_lossy().to_string(),
+            warnings: result.warnings,
+            paper_source: result.paper_source,
+            page_dpis: result.page_dpis,
+            timing: None,
+        })
+    }
+    
+    /// Read the pages of a NAPS2 desktop recovery folder, in order
+    ///
+    /// Works with folders written by the desktop app as well as by
+    /// `scan_to_recovery_folder`. Fails if a page listed in the index is missing,
+    /// or isn't a plain file name inside `folder`.
+    pub fn open_recovery_folder<P: AsRef<Path>>(folder: P) -> Result<ScanResult> {
+        let folder = folder.as_ref();
+        
+        let image_paths = recovery::read_index(folder)?.into_iter()
+            .map(|name| {
+                let path = folder.join(&name);
+                if path.is_file() {
+                    Ok(path.to_string_lossy().to_string())
+                } else {
+                    Err(Naps2Error::ScanningError(format!("Recovery page {:?} is missing", path)).into())
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        
+        Ok(ScanResult {
+            image_paths,
+            temp_directory: folder.to_string_lossy().to_string(),
+            warnings: Vec::new(),
+            paper_source: None,
+            page_dpis: Vec::new(),
+            timing: None,
+        })
+    }
+    
     /// Scan using the specified device and save to images
+    ///
+    /// A `paper_source` the device doesn't offer fails with
+    /// `Naps2Error::UnsupportedPaperSource`; use `scan` with
+    /// `ScanOptions::fallback_to_flatbed` to fall back instead. A scan that
+    /// produces no pages fails with `Naps2Error::NoPagesScanned`; use `scan`
+    /// with `ScanOptions::allow_empty` to get an empty result instead.
     pub fn scan_to_images(&self, device_id: &str, driver: Option<Driver>, dpi: u32, 
                           paper_source: Option<PaperSource>) -> Result<ScanResult> {
-        let mut cmd = Command::new(&self.helper_path);
+        let paper_source = match paper_source {
+            Some(requested) => Some(self.check_paper_source(device_id, driver, requested, false)?.0),
+            None => None,
+        };
+        
+        let mut cmd = self.command();
         cmd.args(["scan", "to-images", device_id]);
         
         // Add driver argument if specified
@@ -139,20 +1930,216 @@ impl ScanClient {
         }
         
         // Execute the helper application
-        let output = cmd.output()
-            .with_context(|| format!("Failed to execute helper at {:?}", self.helper_path))?;
-            
-        // Check if the command was successful
-        if !output.status.success() {
-            let error_message = String::from_utf8_lossy(&output.stderr).to_string();
-            return Err(Naps2Error::HelperExecutionError(error_message).into());
-        }
+        let (output, elapsed) = self.throttled(device_id, || timed_scan(&mut cmd))?;
         
         // Parse the JSON output
         let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let result: ScanResult = serde_json::from_str(&stdout)
-            .map_err(|e| Naps2Error::HelperOutputError(format!("JSON parse error: {}", e)))?;
+        let mut result: ScanResult = helper::parse_json(&stdout)?;
             
+        if result.image_paths.is_empty() {
+            return Err(Naps2Error::NoPagesScanned(format!("{} returned no pages", device_id)).into());
+        }
+        result.paper_source = paper_source;
+        result.fill_page_dpis(dpi);
+        result.timing = Some(ScanTiming::from_output(&stdout, elapsed)?);
+        
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(pages: &[(&str, u32)], timing: Option<&[u64]>) -> ScanResult {
+        ScanResult {
+            image_paths: pages.iter().map(|(path, _)| path.to_string()).collect(),
+            temp_directory: String::new(),
+            warnings: Vec::new(),
+            paper_source: None,
+            page_dpis: pages.iter().map(|(_, dpi)| *dpi).collect(),
+            timing: timing.map(|ms| ScanTiming {
+                total: Duration::from_secs(1),
+                pages: ms.iter().copied().map(Duration::from_millis).collect(),
+            }),
+        }
+    }
+
+    #[test]
+    fn reversed_pages_keep_their_dpi_and_timing() {
+        let mut scan = result(&[("1.jpg", 300), ("2.jpg", 150), ("3.jpg", 600)], Some(&[10, 20, 30]));
+        scan.reverse_pages();
+        assert_eq!(scan.image_paths, ["3.jpg", "2.jpg", "1.jpg"]);
+        assert_eq!(scan.page_dpis, [600, 150, 300]);
+        assert_eq!(scan.page_dpi(0), Some(600));
+        assert_eq!(scan.timing.unwrap().pages, [30, 20, 10].map(Duration::from_millis));
+    }
+
+    #[test]
+    fn reversing_twice_restores_the_order() {
+        let original = result(&[("1.jpg", 300), ("2.jpg", 150)], Some(&[10, 20]));
+        let mut scan = result(&[("1.jpg", 300), ("2.jpg", 150)], Some(&[10, 20]));
+        scan.reverse_pages();
+        scan.reverse_pages();
+        assert_eq!(scan.image_paths, original.image_paths);
+        assert_eq!(scan.page_dpis, original.page_dpis);
+        assert_eq!(scan.timing, original.timing);
+    }
+
+    #[test]
+    fn reversing_without_timing() {
+        let mut scan = result(&[("1.jpg", 300), ("2.jpg", 150)], None);
+        scan.reverse_pages();
+        assert_eq!(scan.image_paths, ["2.jpg", "1.jpg"]);
+        assert_eq!(scan.page_dpis, [150, 300]);
+        assert!(scan.timing.is_none());
+    }
+
+    #[test]
+    fn throttle_permit_holds_the_device_until_dropped() {
+        let throttle = ScanThrottle { interval: Duration::from_millis(50), devices: Mutex::new(HashMap::new()) };
+        let permit = throttle.acquire("scanner");
+        let started = Instant::now();
+        thread::scope(|s| {
+            let waiter = s.spawn(|| {
+                let _permit = throttle.acquire("scanner");
+                started.elapsed()
+            });
+            // Other devices don't wait
+            drop(throttle.acquire("other"));
+            assert!(started.elapsed() < Duration::from_millis(50));
+            
+            thread::sleep(Duration::from_millis(100));
+            drop(permit);
+            assert!(waiter.join().unwrap() >= Duration::from_millis(150));
+        });
+    }
+
+    #[test]
+    fn unused_permit_does_not_delay_the_next_scan() {
+        let throttle = ScanThrottle { interval: Duration::from_secs(60), devices: Mutex::new(HashMap::new()) };
+        throttle.acquire("scanner").release_unused();
+        let started = Instant::now();
+        drop(throttle.acquire("scanner"));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    fn device(driver: &str, id: &str, name: &str, key: Option<&str>) -> ScannerDevice {
+        ScannerDevice {
+            id: id.to_string(),
+            name: name.to_string(),
+            driver: driver.to_string(),
+            key: key.map(str::to_string),
+            alternates: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn same_scanner_under_two_drivers_is_merged() {
+        let escl = device("Escl", "uuid-1", "Canon MF743C", Some("host:192.168.1.20"));
+        let sane = device("Sane", "airscan:e0:Canon", "Canon MF743C (eSCL)", Some("host:192.168.1.20"));
+        let devices = merge_devices(vec![escl.clone(), sane.clone()]);
+        assert_eq!(devices, [ScannerDevice { alternates: vec![sane], ..escl }]);
+    }
+
+    #[test]
+    fn scanners_with_the_same_name_stay_separate() {
+        let devices = merge_devices(vec![
+            device("Escl", "uuid-1", "Canon MF743C", Some("host:192.168.1.20")),
+            device("Escl", "uuid-2", "Canon MF743C", Some("host:192.168.1.21")),
+            device("Sane", "pixma:04A91234", "Canon MF743C", None),
+            device("Wia", "{6BDD1FC6}\\0000", "Canon MF743C", None),
+            device("escl", "uuid-1", "Canon MF743C (office)", Some("host:192.168.1.20")),
+        ]);
+        assert_eq!(devices.iter().map(|d| (d.driver.as_str(), d.id.as_str(), d.alternates.len())).collect::<Vec<_>>(),
+                   [("Escl", "uuid-1", 0), ("Escl", "uuid-2", 0), ("Sane", "pixma:04A91234", 0), ("Wia", "{6BDD1FC6}\\0000", 0)]);
+    }
+
+    #[test]
+    fn preview_bounds_keep_their_offset() {
+        let bounds = Rect::new(150, 30, 250, 150);
+        assert_eq!(scale_preview_bounds(&bounds, 600, 10_000, 10_000), Rect::new(1200, 240, 2000, 1200));
+    }
+
+    #[test]
+    fn preview_bounds_are_clamped_to_the_page() {
+        let bounds = Rect::new(600, 800, 38, 77);
+        assert_eq!(scale_preview_bounds(&bounds, 600, 5100, 7000), Rect::new(4800, 6400, 300, 600));
+        assert_eq!(scale_preview_bounds(&bounds, 600, 4000, 6000), Rect::new(3999, 5999, 1, 1));
+    }
+
+    #[test]
+    fn scan_to_sink_rejects_reverse_order() {
+        let client = ScanClient::new(PathBuf::from("/nonexistent/naps2-helper"));
+        let options = ScanOptions { reverse_order: true, ..ScanOptions::default() };
+        let mut sink = crate::bindings::sink::FileSink::new(std::env::temp_dir());
+        let error = client.scan_to_sink("device", &options, &mut sink).unwrap_err();
+        assert!(matches!(error.downcast_ref::<Naps2Error>(), Some(Naps2Error::InvalidOptionsError(_))), "{}", error);
+    }
+
+    /// Write `script` to `dir` as an executable stand-in for the helper
+    #[cfg(unix)]
+    fn script_helper(dir: &Path, script: &str) -> ScanClient {
+        use std::os::unix::fs::PermissionsExt;
+        
+        let helper = dir.join("helper.sh");
+        fs::write(&helper, script).unwrap();
+        fs::set_permissions(&helper, fs::Permissions::from_mode(0o755)).unwrap();
+        ScanClient::new(helper)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resumed_scan_reverses_every_session_together() {
+        let dir = std::env::temp_dir().join(format!("naps2_resume_test_{}", std::process::id()));
+        let checkpoint = dir.join("checkpoint");
+        fs::create_dir_all(&checkpoint).unwrap();
+        for page in ["page1.jpg", "page2.jpg"] {
+            fs::write(checkpoint.join(page), b"").unwrap();
+        }
+        
+        // Stands in for the helper, saving one more page to the checkpoint directory
+        let client = script_helper(&dir, "#!/bin/sh\nwhile [ \"$1\" != --checkpoint-dir ]; do shift; done\n\
+                            touch \"$2/page3.jpg\"\nprintf '{\"ImagePaths\":[\"%s/page3.jpg\"],\"TempDirectory\":\"%s\"}' \"$2\" \"$2\"\n");
+        
+        let options = ScanOptions { reverse_order: true, ..ScanOptions::default() };
+        let result = client.resume_or_scan("device", &options, &checkpoint).unwrap();
+        let names: Vec<_> = result.image_paths.iter()
+            .map(|path| Path::new(path).file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, ["page3.jpg", "page2.jpg", "page1.jpg"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resumed_scan_without_any_pages_fails_unless_allowed() {
+        let dir = std::env::temp_dir().join(format!("naps2_resume_empty_test_{}", std::process::id()));
+        let checkpoint = dir.join("checkpoint");
+        fs::create_dir_all(&checkpoint).unwrap();
+        let client = script_helper(&dir, "#!/bin/sh\nprintf '{\"ImagePaths\":[],\"TempDirectory\":\"\"}'\n");
+        
+        let error = client.resume_or_scan("device", &ScanOptions::default(), &checkpoint).unwrap_err();
+        assert!(matches!(error.downcast_ref::<Naps2Error>(), Some(Naps2Error::NoPagesScanned(_))), "{}", error);
+        let options = ScanOptions { allow_empty: true, ..ScanOptions::default() };
+        assert!(client.resume_or_scan("device", &options, &checkpoint).unwrap().image_paths.is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn recovery_index_names_must_stay_inside_the_folder() {
+        let folder = std::env::temp_dir().join(format!("naps2_recovery_test_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("page-0001.jpg"), b"").unwrap();
+        recovery::write_index(&folder, &["page-0001.jpg".to_string()], ColorMode::Color).unwrap();
+        let opened = ScanClient::open_recovery_folder(&folder).unwrap();
+        assert_eq!(opened.image_paths, [folder.join("page-0001.jpg").to_string_lossy()]);
+        
+        for name in ["../page-0001.jpg", "/etc/passwd", "pages/page-0001.jpg", ".", ""] {
+            recovery::write_index(&folder, &[name.to_string()], ColorMode::Color).unwrap();
+            let error = ScanClient::open_recovery_folder(&folder).unwrap_err();
+            assert!(error.to_string().contains("Invalid FileName"), "{}: {}", name, error);
+        }
+        let _ = fs::remove_dir_all(&folder);
+    }
+}
@@ -0,0 +1,79 @@
+//! Destinations that receive scanned pages as they are produced
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::bindings::images::ImageFormat;
+use crate::bindings::naming::{NamingContext, OutputNaming, OverwritePolicy, SavedFile, SequentialNaming};
+use crate::bindings::pdf::iso8601;
+
+/// Receives each page of a scan from `ScanClient::scan_to_sink`
+///
+/// Implement this to send pages straight to an object store, a database or a
+/// network service: the crate hands over each page's encoded bytes as soon as
+/// the driver has saved it and deletes its own copy afterwards. An error
+/// stops the scan.
+pub trait PageSink {
+    /// Take the page at zero-based `index`, encoded as `format`
+    fn accept_page(&mut self, index: usize, bytes: &[u8], format: ImageFormat) -> Result<()>;
+}
+
+/// Writes each page to a folder, as `page-0001.jpg`, `page-0002.jpg`...
+///
+/// Names that already exist are handled by the sink's `OverwritePolicy`,
+/// `Rename` unless set with `with_overwrite`.
+#[derive(Debug, Clone)]
+pub struct FileSink {
+    folder: PathBuf,
+    naming: SequentialNaming,
+    overwrite: OverwritePolicy,
+    timestamp: String,
+    files: Vec<SavedFile>,
+}
+
+impl FileSink {
+    /// Write pages to `folder`, creating it if needed
+    pub fn new<P: Into<PathBuf>>(folder: P) -> Self {
+        Self {
+            folder: folder.into(),
+            naming: SequentialNaming::default(),
+            overwrite: OverwritePolicy::default(),
+            timestamp: iso8601(std::time::SystemTime::now()),
+            files: Vec::new(),
+        }
+    }
+
+    /// Set what happens when a page's file name already exists
+    pub fn with_overwrite(mut self, policy: OverwritePolicy) -> Self {
+        self.overwrite = policy;
+        self
+    }
+
+    /// Folder the pages are written to
+    pub fn folder(&self) -> &Path {
+        &self.folder
+    }
+
+    /// Outcome of each page so far, in the order the pages arrived
+    pub fn files(&self) -> &[SavedFile] {
+        &self.files
+    }
+}
+
+impl PageSink for FileSink {
+    fn accept_page(&mut self, index: usize, bytes: &[u8], format: ImageFormat) -> Result<()> {
+        fs::create_dir_all(&self.folder)
+            .with_context(|| format!("Failed to create output folder {:?}", self.folder))?;
+        let context = NamingContext { source: None, timestamp: &self.timestamp };
+        let intended = self.folder.join(format!("{}.{}", self.naming.name(index, &context), format.extension()));
+        let (target, action) = self.overwrite.resolve(intended.clone());
+        if let Some(target) = &target {
+            fs::write(target, bytes).with_context(|| format!("Failed to write page to {:?}", target))?;
+        }
+        self.files.push(SavedFile {
+            path: target.unwrap_or(intended).to_string_lossy().to_string(),
+            action,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+//! Page-by-page access to multi-page TIFF files
+//!
+//! Only the image file directories (IFDs) are read, one at a time, so listing
+//! the pages of a large TIFF costs a few hundred bytes per page regardless of
+//! the image data. Pages are decoded individually by the helper with
+//! `ImageClient::extract_tiff_page`. BigTIFF files are not supported.
+
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use crate::bindings::error::Naps2Error;
+use crate::bindings::resolution::ByteOrder;
+
+const TIFF_IMAGE_WIDTH: u16 = 256;
+const TIFF_IMAGE_LENGTH: u16 = 257;
+const TIFF_COMPRESSION: u16 = 259;
+const TIFF_TYPE_SHORT: u16 = 3;
+const TIFF_TYPE_LONG: u16 = 4;
+
+/// Header information for one page of a TIFF
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TiffPage {
+    /// Zero-based page number, as passed to `ImageClient::extract_tiff_page`
+    pub index: usize,
+    pub width: u32,
+    pub height: u32,
+    /// TIFF compression code, e.g. 1 for none, 4 for CCITT Group 4, 5 for LZW, 7 for JPEG
+    pub compression: u16,
+}
+
+/// Iterator over the pages of a TIFF, reading each page's header only when reached
+#[derive(Debug)]
+pub struct TiffPages {
+    path: PathBuf,
+    reader: BufReader<File>,
+    order: ByteOrder,
+    next_ifd: u64,
+    index: usize,
+    visited: Vec<u64>,
+}
+
+impl TiffPages {
+    /// Open a TIFF and read its file header
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut reader = BufReader::new(File::open(&path)
+            .with_context(|| format!("Failed to open image {:?}", path))?);
+        
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)
+            .map_err(|_| tiff_error(&path, "file is too short"))?;
+        let order = match &header[0..2] {
+            b"II" => ByteOrder::Little,
+            b"MM" => ByteOrder::Big,
+            _ => return Err(tiff_error(&path, "not a TIFF file")),
+        };
+        match order.u16(&header, 2) {
+            Some(42) => {}
+            Some(43) => return Err(tiff_error(&path, "BigTIFF not supported")),
+            _ => return Err(tiff_error(&path, "not a TIFF file")),
+        }
+        let next_ifd = order.u32(&header, 4).unwrap_or(0) as u64;
+        
+        Ok(Self { path, reader, order, next_ifd, index: 0, visited: Vec::new() })
+    }
+    
+    /// Read the IFD at `next_ifd` and advance to the one after it
+    fn read_page(&mut self) -> Result<TiffPage> {
+        let offset = self.next_ifd;
+        self.visited.push(offset);
+        
+        let mut count = [0u8; 2];
+        self.reader.seek(SeekFrom::Start(offset))
+            .and_then(|_| self.reader.read_exact(&mut count))
+            .map_err(|_| tiff_error(&self.path, "directory offset is past the end of the file"))?;
+        let count = self.order.u16(&count, 0).unwrap_or(0) as usize;
+        
+        // Entries are 12 bytes each, followed by the offset of the next IFD
+        let mut entries = vec![0u8; count * 12 + 4];
+        self.reader.read_exact(&mut entries)
+            .map_err(|_| tiff_error(&self.path, "truncated directory"))?;
+        
+        let mut page = TiffPage { index: self.index, width: 0, height: 0, compression: 1 };
+        for entry in entries[..count * 12].chunks_exact(12) {
+            let tag = self.order.u16(entry, 0).unwrap_or(0);
+            let value = match self.order.u16(entry, 2) {
+                Some(TIFF_TYPE_SHORT) => self.order.u16(entry, 8).map(u32::from),
+                Some(TIFF_TYPE_LONG) => self.order.u32(entry, 8),
+                _ => None,
+            };
+            match (tag, value) {
+                (TIFF_IMAGE_WIDTH, Some(value)) => page.width = value,
+                (TIFF_IMAGE_LENGTH, Some(value)) => page.height = value,
+                (TIFF_COMPRESSION, Some(value)) => page.compression = value as u16,
+                _ => {}
+            }
+        }
+        
+        self.next_ifd = self.order.u32(&entries, count * 12).unwrap_or(0) as u64;
+        self.index += 1;
+        Ok(page)
+    }
+}
+
+impl Iterator for TiffPages {
+    type Item = Result<TiffPage>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        // A directory chain that loops back on itself ends the iteration
+        if self.next_ifd == 0 || self.visited.contains(&self.next_ifd) {
+            return None;
+        }
+        
+        let page = self.read_page();
+        if page.is_err() {
+            self.next_ifd = 0;
+        }
+        Some(page)
+    }
+}
+
+fn tiff_error(path: &Path, reason: &str) -> anyhow::Error {
+    Naps2Error::ImageError(format!("Cannot read TIFF {:?}: {}", path, reason)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A TIFF with one IFD per page; the last IFD links to `last_next` instead of ending the chain
+    fn tiff(big_endian: bool, pages: &[(u32, u16, u16)], last_next: u32) -> Vec<u8> {
+        let u16_bytes = |value: u16| if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+        let u32_bytes = |value: u32| if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+        
+        let mut data = if big_endian { b"MM".to_vec() } else { b"II".to_vec() };
+        data.extend_from_slice(&u16_bytes(42));
+        data.extend_from_slice(&u32_bytes(8));
+        for (index, &(width, height, compression)) in pages.iter().enumerate() {
+            data.extend_from_slice(&u16_bytes(3));
+            for (tag, kind, value) in [(TIFF_IMAGE_WIDTH, TIFF_TYPE_LONG, width),
+                                       (TIFF_IMAGE_LENGTH, TIFF_TYPE_SHORT, height as u32),
+                                       (TIFF_COMPRESSION, TIFF_TYPE_SHORT, compression as u32)] {
+                data.extend_from_slice(&u16_bytes(tag));
+                data.extend_from_slice(&u16_bytes(kind));
+                data.extend_from_slice(&u32_bytes(1));
+                // SHORT values sit in the first two bytes of the value field
+                if kind == TIFF_TYPE_SHORT {
+                    data.extend_from_slice(&u16_bytes(value as u16));
+                    data.extend_from_slice(&[0, 0]);
+                } else {
+                    data.extend_from_slice(&u32_bytes(value));
+                }
+            }
+            let next = if index + 1 == pages.len() { last_next } else { data.len() as u32 + 4 };
+            data.extend_from_slice(&u32_bytes(next));
+        }
+        data
+    }
+
+    fn pages(name: &str, data: &[u8]) -> Result<Vec<Result<TiffPage>>> {
+        let path = std::env::temp_dir().join(format!("naps2_tiff_test_{}_{}.tif", std::process::id(), name));
+        std::fs::write(&path, data).unwrap();
+        let pages = TiffPages::open(&path).map(|pages| pages.collect());
+        let _ = std::fs::remove_file(&path);
+        pages
+    }
+
+    #[test]
+    fn both_byte_orders_read_the_same_pages() {
+        let expected = [(2480, 3508, 4), (1240, 1754, 5)];
+        for big_endian in [false, true] {
+            let read: Vec<_> = pages(&format!("order_{}", big_endian), &tiff(big_endian, &expected, 0)).unwrap()
+                .into_iter().map(Result::unwrap).collect();
+            assert_eq!(read, vec![
+                TiffPage { index: 0, width: 2480, height: 3508, compression: 4 },
+                TiffPage { index: 1, width: 1240, height: 1754, compression: 5 },
+            ]);
+        }
+    }
+
+    #[test]
+    fn directory_loop_ends_the_iteration() {
+        // The second page links back to the first
+        let read = pages("loop", &tiff(false, &[(10, 10, 1), (20, 20, 1)], 8)).unwrap();
+        assert_eq!(read.len(), 2);
+        assert!(read.iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn truncated_directory_is_an_error_and_ends_the_iteration() {
+        let mut data = tiff(false, &[(10, 10, 1), (20, 20, 1)], 0);
+        data.truncate(data.len() - 10);
+        let read = pages("truncated", &data).unwrap();
+        assert_eq!(read.len(), 2);
+        assert!(read[0].is_ok());
+        assert!(read[1].as_ref().unwrap_err().to_string().contains("truncated directory"));
+    }
+
+    #[test]
+    fn bigtiff_is_rejected_explicitly() {
+        let mut data = tiff(false, &[(10, 10, 1)], 0);
+        data[2] = 43;
+        let error = pages("bigtiff", &data).unwrap_err();
+        assert!(error.to_string().contains("BigTIFF not supported"), "{}", error);
+    }
+}